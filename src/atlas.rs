@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use image::{GenericImageView, RgbaImage};
+use serde::{Deserialize, Serialize};
+use crate::error::Result;
+use crate::model2arr::Block;
+use super::float;
+
+/// Where a block's texture is in the atlas, in pixels and normalized UVs.
+///
+/// UVs have their origin at the top left of the atlas (like glTF), flip v for OBJ or Unity.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UvRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    pub u0: float,
+    pub v0: float,
+    pub u1: float,
+    pub v1: float,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AtlasLayout {
+    pub width: u32,
+    pub height: u32,
+    pub rects: BTreeMap<Block, UvRect>,
+}
+
+pub struct Atlas {
+    pub image: RgbaImage,
+    pub layout: AtlasLayout,
+}
+
+impl Atlas {
+    /// Packs the textures from `load_textures` into one image.
+    ///
+    /// Every texture is surrounded by `padding` pixels copied from its edge, so filtering and mip
+    /// maps don't bleed neighbouring textures in. The atlas size is a power of two.
+    pub fn build(textures: &[(Block, image::DynamicImage)], padding: u32) -> Self {
+        // Shelf packing, tallest textures first.
+        let mut order = (0..textures.len()).collect::<Vec<usize>>();
+        order.sort_by_key(|&i| (std::cmp::Reverse(textures[i].1.height()), textures[i].0));
+
+        let padded = |i: usize| {
+            let (w, h) = textures[i].1.dimensions();
+            (w + 2 * padding, h + 2 * padding)
+        };
+        let area = order.iter().map(|&i| padded(i).0 as u64 * padded(i).1 as u64).sum::<u64>();
+        let widest = order.iter().map(|&i| padded(i).0).max().unwrap_or(1);
+        let width = ((area as f64).sqrt().ceil() as u32).max(widest).next_power_of_two();
+
+        let mut positions = vec![(0, 0); textures.len()];
+        let (mut x, mut y, mut shelf_h) = (0, 0, 0);
+        for &i in order.iter() {
+            let (w, h) = padded(i);
+            if x + w > width {
+                x = 0;
+                y += shelf_h;
+                shelf_h = 0;
+            }
+            positions[i] = (x, y);
+            x += w;
+            shelf_h = shelf_h.max(h);
+        }
+        let height = (y + shelf_h).max(1).next_power_of_two();
+
+        let mut image = RgbaImage::new(width, height);
+        let mut rects = BTreeMap::new();
+        for (i, (block, texture)) in textures.iter().enumerate() {
+            let texture = texture.to_rgba8();
+            let (w, h) = texture.dimensions();
+            let (px, py) = positions[i];
+            for dy in 0..h + 2 * padding {
+                for dx in 0..w + 2 * padding {
+                    let sx = dx.saturating_sub(padding).min(w.saturating_sub(1));
+                    let sy = dy.saturating_sub(padding).min(h.saturating_sub(1));
+                    if w > 0 && h > 0 {
+                        image.put_pixel(px + dx, py + dy, *texture.get_pixel(sx, sy));
+                    }
+                }
+            }
+
+            let (x, y) = (px + padding, py + padding);
+            rects.insert(*block, UvRect {
+                x,
+                y,
+                w,
+                h,
+                u0: x as float / width as float,
+                v0: y as float / height as float,
+                u1: (x + w) as float / width as float,
+                v1: (y + h) as float / height as float,
+            });
+        }
+
+        Self { image, layout: AtlasLayout { width, height, rects } }
+    }
+
+    pub fn uv(&self, block: Block) -> Option<&UvRect> {
+        self.layout.rects.get(&block)
+    }
+
+    /// Writes the atlas image and the JSON of UV rectangles per block.
+    pub fn save<P, Q>(&self, png_path: P, json_path: Q) -> Result<()>
+    where
+            P: AsRef<std::path::Path> + std::fmt::Debug,
+            Q: AsRef<std::path::Path> + std::fmt::Debug, {
+        self.image.save(png_path)?;
+        std::fs::write(json_path, serde_json::to_string(&self.layout)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, Rgba};
+
+    #[test]
+    fn packs_without_overlap() {
+        let textures = (1..=5)
+            .map(|b| (b, DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 8 * b as u32, Rgba([b as u8, 0, 0, 255])))))
+            .collect::<Vec<_>>();
+        let atlas = Atlas::build(&textures, 2);
+        assert!(atlas.layout.width.is_power_of_two() && atlas.layout.height.is_power_of_two());
+        let rects = atlas.layout.rects.values().collect::<Vec<_>>();
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.x + a.w <= atlas.layout.width && a.y + a.h <= atlas.layout.height);
+            for b in rects[i + 1..].iter() {
+                let apart = a.x + a.w + 2 <= b.x || b.x + b.w + 2 <= a.x || a.y + a.h + 2 <= b.y || b.y + b.h + 2 <= a.y;
+                assert!(apart);
+            }
+        }
+        let uv = atlas.uv(3).unwrap();
+        assert_eq!((uv.w, uv.h), (16, 24));
+        // Padding repeats the edge of the texture.
+        assert_eq!(atlas.image.get_pixel(uv.x - 1, uv.y - 1).0, [3, 0, 0, 255]);
+    }
+}
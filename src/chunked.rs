@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use crate::grid::{GridTransform, VoxelGrid};
+use crate::registry::BlockRegistry;
+use crate::model2arr::{ArrayModel, Block, CoordXYZ};
+use super::float;
+
+/// Chunks are CHUNK_SIZE voxels along every axis.
+pub const CHUNK_SIZE: usize = 16;
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+/// Paletted chunks switch to storing blocks directly once they hold more distinct blocks than this.
+const MAX_PALETTE_LEN: usize = u8::MAX as usize + 1;
+
+pub type ChunkCoord = (usize, usize, usize);
+
+#[derive(Debug, Clone)]
+enum ChunkData {
+    /// Every voxel in the chunk is the same block.
+    Uniform(Block),
+    /// Like a Minecraft section, voxels index into a palette of the blocks used in the chunk.
+    Paletted { palette: Vec<Block>, indices: Vec<u8> },
+    Direct(Vec<Block>),
+}
+
+#[derive(Debug, Clone)]
+struct Chunk {
+    data: ChunkData,
+    /// Number of non air voxels, the chunk is dropped once this reaches 0.
+    filled: usize,
+}
+
+impl Chunk {
+    fn get(&self, i: usize) -> Block {
+        match &self.data {
+            ChunkData::Uniform(b) => *b,
+            ChunkData::Paletted { palette, indices } => palette[indices[i] as usize],
+            ChunkData::Direct(blocks) => blocks[i],
+        }
+    }
+
+    fn set(&mut self, i: usize, val: Block) -> Block {
+        let prev = self.get(i);
+        if prev == val {
+            return prev;
+        }
+        if prev == 0 {
+            self.filled += 1;
+        } else if val == 0 {
+            self.filled -= 1;
+        }
+
+        match &mut self.data {
+            ChunkData::Uniform(b) => {
+                let mut indices = vec![0; CHUNK_VOLUME];
+                indices[i] = 1;
+                self.data = ChunkData::Paletted { palette: vec![*b, val], indices };
+            }
+            ChunkData::Paletted { palette, indices } => {
+                let p = match palette.iter().position(|&b| b == val) {
+                    Some(p) => p,
+                    None => {
+                        if palette.len() == MAX_PALETTE_LEN {
+                            Self::compact_palette(palette, indices);
+                        }
+                        palette.push(val);
+                        palette.len() - 1
+                    }
+                };
+                if p < MAX_PALETTE_LEN {
+                    indices[i] = p as u8;
+                } else {
+                    let mut blocks = indices.iter().map(|&p| palette[p as usize]).collect::<Vec<Block>>();
+                    blocks[i] = val;
+                    self.data = ChunkData::Direct(blocks);
+                }
+            }
+            ChunkData::Direct(blocks) => blocks[i] = val,
+        }
+        prev
+    }
+
+    /// Removes palette entries which are no longer referenced.
+    fn compact_palette(palette: &mut Vec<Block>, indices: &mut [u8]) {
+        let mut used = vec![false; palette.len()];
+        for &p in indices.iter() {
+            used[p as usize] = true;
+        }
+        let mut remap = vec![0u8; palette.len()];
+        let mut compacted = Vec::with_capacity(palette.len());
+        for (p, &b) in palette.iter().enumerate() {
+            if used[p] {
+                remap[p] = compacted.len() as u8;
+                compacted.push(b);
+            }
+        }
+        for p in indices.iter_mut() {
+            *p = remap[*p as usize];
+        }
+        *palette = compacted;
+    }
+}
+
+/// Sparse voxel storage made of CHUNK_SIZE^3 chunks, only chunks containing non air voxels are
+/// allocated. Has the same get/set API as `ArrayModel`.
+#[derive(Debug, Clone)]
+pub struct ChunkedModel {
+    chunks: HashMap<ChunkCoord, Chunk>,
+    pub dims: CoordXYZ,
+    pub resolution: float,
+    pub transform: GridTransform,
+    pub registry: BlockRegistry,
+}
+
+impl ChunkedModel {
+    pub fn new(dims: CoordXYZ, resolution: float) -> Self {
+        Self::with_transform(dims, GridTransform::default(), resolution)
+    }
+
+    pub fn with_transform(dims: CoordXYZ, transform: GridTransform, resolution: float) -> Self {
+        Self { chunks: HashMap::new(), dims, resolution, transform, registry: BlockRegistry::default() }
+    }
+
+    pub fn from_array(arr: &ArrayModel) -> Self {
+        let mut chunked = Self::with_transform(arr.dims, arr.transform.clone(), arr.resolution);
+        chunked.registry = arr.registry.clone();
+        for y in 0..arr.dims.1 as usize {
+            for x in 0..arr.dims.0 as usize {
+                for (z, &b) in arr.row(x, y).unwrap_or(&[]).iter().enumerate() {
+                    if b != 0 {
+                        chunked.set((x, y, z), b);
+                    }
+                }
+            }
+        }
+        chunked
+    }
+
+    pub fn to_array(&self) -> ArrayModel {
+        let mut arr = ArrayModel::with_transform(self.dims, self.transform.clone(), self.resolution);
+        arr.registry = self.registry.clone();
+        for (&(cx, cy, cz), chunk) in self.chunks.iter() {
+            for i in 0..CHUNK_VOLUME {
+                let b = chunk.get(i);
+                if b != 0 {
+                    let (x, y, z) = Self::local_coord(i);
+                    arr.set((cx * CHUNK_SIZE + x, cy * CHUNK_SIZE + y, cz * CHUNK_SIZE + z), b);
+                }
+            }
+        }
+        arr
+    }
+
+    /// Number of allocated chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    fn split(c_xyz: (usize, usize, usize)) -> (ChunkCoord, usize) {
+        let chunk = (c_xyz.0 / CHUNK_SIZE, c_xyz.1 / CHUNK_SIZE, c_xyz.2 / CHUNK_SIZE);
+        let (x, y, z) = (c_xyz.0 % CHUNK_SIZE, c_xyz.1 % CHUNK_SIZE, c_xyz.2 % CHUNK_SIZE);
+        (chunk, (y * CHUNK_SIZE + x) * CHUNK_SIZE + z)
+    }
+
+    fn local_coord(i: usize) -> (usize, usize, usize) {
+        (i / CHUNK_SIZE % CHUNK_SIZE, i / (CHUNK_SIZE * CHUNK_SIZE), i % CHUNK_SIZE)
+    }
+
+    /// c_xyz: (x, y, z)
+    pub fn get(&self, c_xyz: (usize, usize, usize)) -> Option<Block> {
+        if !self.in_bounds(c_xyz) {
+            return None;
+        }
+        let (chunk, i) = Self::split(c_xyz);
+        Some(self.chunks.get(&chunk).map_or(0, |c| c.get(i)))
+    }
+
+    /// Returns the previous block, or `None` if out of bounds.
+    /// c_xyz: (x, y, z)
+    pub fn set(&mut self, c_xyz: (usize, usize, usize), val: Block) -> Option<Block> {
+        if !self.in_bounds(c_xyz) {
+            return None;
+        }
+        let (coord, i) = Self::split(c_xyz);
+        let chunk = match self.chunks.get_mut(&coord) {
+            Some(chunk) => chunk,
+            None if val == 0 => return Some(0),
+            None => self.chunks
+                .entry(coord)
+                .or_insert(Chunk { data: ChunkData::Uniform(0), filled: 0 }),
+        };
+        let prev = chunk.set(i, val);
+        if chunk.filled == 0 {
+            self.chunks.remove(&coord);
+        }
+        Some(prev)
+    }
+}
+
+impl VoxelGrid for ChunkedModel {
+    fn dims(&self) -> CoordXYZ {
+        self.dims
+    }
+
+    fn resolution(&self) -> float {
+        self.resolution
+    }
+
+    fn transform(&self) -> &GridTransform {
+        &self.transform
+    }
+
+    fn registry(&self) -> &BlockRegistry {
+        &self.registry
+    }
+
+    fn get(&self, c_xyz: (usize, usize, usize)) -> Option<Block> {
+        ChunkedModel::get(self, c_xyz)
+    }
+
+    fn set(&mut self, c_xyz: (usize, usize, usize), val: Block) -> Option<Block> {
+        ChunkedModel::set(self, c_xyz, val)
+    }
+
+    fn iter_filled(&self) -> Box<dyn Iterator<Item = ((usize, usize, usize), Block)> + '_> {
+        let dims = self.dims;
+        Box::new(self.chunks.iter().flat_map(move |(&(cx, cy, cz), chunk)| {
+            (0..CHUNK_VOLUME).filter_map(move |i| {
+                let b = chunk.get(i);
+                let (x, y, z) = Self::local_coord(i);
+                let c_xyz = (cx * CHUNK_SIZE + x, cy * CHUNK_SIZE + y, cz * CHUNK_SIZE + z);
+                let inside = c_xyz.0 < dims.0 as usize && c_xyz.1 < dims.1 as usize && c_xyz.2 < dims.2 as usize;
+                (b != 0 && inside).then_some((c_xyz, b))
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Faces, Model, Points};
+    use crate::model2arr::{model_2_arr_into, model_2_arr_with_transform};
+    use crate::registry::DEFAULT_BLOCK;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn array_round_trip_and_empty_chunks() {
+        let mut arr = ArrayModel::new((40, 20, 33), 1.0);
+        arr.set((0, 0, 0), 1);
+        arr.set((39, 19, 32), 2);
+        let mut chunked = ChunkedModel::from_array(&arr);
+        assert_eq!(chunked.chunk_count(), 2);
+        assert_eq!(chunked.to_array().blocks, arr.blocks);
+
+        assert_eq!(chunked.set((39, 19, 32), 0), Some(2));
+        assert_eq!(chunked.chunk_count(), 1);
+        assert_eq!(chunked.get((40, 0, 0)), None);
+        assert!(chunked.try_set((0, 20, 0), 1).is_err());
+    }
+
+    #[test]
+    fn palette_overflow() {
+        // More than 256 different blocks in one chunk.
+        let mut chunked = ChunkedModel::new((16, 16, 16), 1.0);
+        for i in 0..300usize {
+            chunked.set((i % 16, i / 16 % 16, i / 256), i as Block + 1);
+        }
+        for i in 0..300usize {
+            assert_eq!(chunked.get((i % 16, i / 16 % 16, i / 256)), Some(i as Block + 1));
+        }
+    }
+
+    #[test]
+    fn voxelizes_like_array_model() {
+        let model = Model::new(
+            Points(vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(10.0, 0.0, 0.0),
+                Vec3::new(0.0, 10.0, 0.0),
+                Vec3::new(0.0, 0.0, 10.0),
+            ]),
+            Faces(vec![[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]]),
+        );
+        let transform = GridTransform::uniform(Vec3::new(-1.0, -1.0, -1.0), 0.5);
+        let (arr, report) = model_2_arr_with_transform(&model, (24, 24, 24), transform.clone(), 1.0).unwrap();
+        let mut chunked = ChunkedModel::with_transform((24, 24, 24), transform, 1.0);
+        let chunked_report = model_2_arr_into(&mut chunked, &model, DEFAULT_BLOCK).unwrap();
+        assert_eq!(
+            (chunked_report.triangles, chunked_report.clipped_triangles),
+            (report.triangles, report.clipped_triangles),
+        );
+        assert!(chunked.chunk_count() > 1);
+        assert_eq!(chunked.to_array().blocks, arr.blocks);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+use crate::grid::{offset_coord, Neighbourhood};
+use crate::model2arr::{ArrayModel, Block};
+
+/// Which voxels belong to the same region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Match {
+    /// Voxels with exactly the same block.
+    Block,
+    /// Any non air voxels, or air voxels when starting from air.
+    Occupancy,
+}
+
+impl Match {
+    fn same(&self, a: Block, b: Block) -> bool {
+        match self {
+            Self::Block => a == b,
+            Self::Occupancy => (a != 0) == (b != 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Component {
+    /// Label of the component, starts at 1.
+    pub id: u32,
+    pub size: usize,
+    /// Inclusive bounding box.
+    pub min: (usize, usize, usize),
+    pub max: (usize, usize, usize),
+    /// Block of the first voxel found, i.e. the block of every voxel when matching by `Match::Block`.
+    pub block: Block,
+    /// Whether the component touches the edge of the grid.
+    pub touches_boundary: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Labels {
+    /// Label per voxel, in the same layout as `ArrayModel::blocks`. 0 is unlabelled.
+    pub labels: Vec<u32>,
+    /// Component `id` is at `components[id - 1]`.
+    pub components: Vec<Component>,
+}
+
+impl Labels {
+    pub fn largest(&self) -> Option<&Component> {
+        self.components.iter().max_by_key(|c| c.size)
+    }
+}
+
+/// Replaces the region connected to `start` by `val`, returns the number of voxels changed.
+/// c_xyz: (x, y, z)
+pub fn flood_fill(
+    arr: &mut ArrayModel,
+    start: (usize, usize, usize),
+    val: Block,
+    mode: Match,
+    neighbourhood: Neighbourhood,
+) -> usize {
+    let Some(start_block) = arr.get(start) else {
+        return 0;
+    };
+    if mode == Match::Block && start_block == val {
+        return 0;
+    }
+    let region = region(arr, start, mode, neighbourhood);
+    let mut changed = 0;
+    for &i in region.iter() {
+        if arr.blocks[i] != val {
+            arr.blocks[i] = val;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Indices of the voxels connected to `start`.
+fn region(arr: &ArrayModel, start: (usize, usize, usize), mode: Match, neighbourhood: Neighbourhood) -> Vec<usize> {
+    let offsets = neighbourhood.offsets();
+    let Some(start_i) = arr.index(start) else {
+        return vec![];
+    };
+    let start_block = arr.blocks[start_i];
+    let mut visited = vec![false; arr.blocks.len()];
+    let mut queue = VecDeque::from([start]);
+    let mut region = vec![start_i];
+    visited[start_i] = true;
+    while let Some(c_xyz) = queue.pop_front() {
+        for &d in offsets.iter() {
+            if let Some(n) = offset_coord(c_xyz, d, arr.dims) {
+                let j = arr.index(n).unwrap();
+                if !visited[j] && mode.same(start_block, arr.blocks[j]) {
+                    visited[j] = true;
+                    region.push(j);
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+    region
+}
+
+/// Labels the connected components of non air voxels.
+pub fn label_components(arr: &ArrayModel, mode: Match, neighbourhood: Neighbourhood) -> Labels {
+    label(arr, mode, neighbourhood, |b| b != 0)
+}
+
+/// Labels the connected regions of air, `Neighbourhood::Six` keeps pockets which are only closed
+/// off diagonally separate.
+pub fn label_air(arr: &ArrayModel, neighbourhood: Neighbourhood) -> Labels {
+    label(arr, Match::Occupancy, neighbourhood, |b| b == 0)
+}
+
+/// Air regions which do not touch the edge of the grid, i.e. are fully enclosed by the model.
+pub fn enclosed_air(arr: &ArrayModel, neighbourhood: Neighbourhood) -> Vec<Component> {
+    label_air(arr, neighbourhood)
+        .components
+        .into_iter()
+        .filter(|c| !c.touches_boundary)
+        .collect()
+}
+
+fn label(arr: &ArrayModel, mode: Match, neighbourhood: Neighbourhood, include: impl Fn(Block) -> bool) -> Labels {
+    let offsets = neighbourhood.offsets();
+    let faces = Neighbourhood::Six.offsets();
+    let mut labels = vec![0u32; arr.blocks.len()];
+    let mut components = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for (i, &b) in arr.blocks.iter().enumerate() {
+        if labels[i] != 0 || !include(b) {
+            continue;
+        }
+        let id = components.len() as u32 + 1;
+        let start = arr.coord(i);
+        let mut component = Component {
+            id,
+            size: 0,
+            min: start,
+            max: start,
+            block: b,
+            touches_boundary: false,
+        };
+        labels[i] = id;
+        queue.push_back(start);
+        while let Some(c_xyz) = queue.pop_front() {
+            component.size += 1;
+            component.min = (component.min.0.min(c_xyz.0), component.min.1.min(c_xyz.1), component.min.2.min(c_xyz.2));
+            component.max = (component.max.0.max(c_xyz.0), component.max.1.max(c_xyz.1), component.max.2.max(c_xyz.2));
+            if faces.iter().any(|&d| offset_coord(c_xyz, d, arr.dims).is_none()) {
+                component.touches_boundary = true;
+            }
+            for &d in offsets.iter() {
+                if let Some(n) = offset_coord(c_xyz, d, arr.dims) {
+                    let j = arr.index(n).unwrap();
+                    if labels[j] == 0 && include(arr.blocks[j]) && mode.same(b, arr.blocks[j]) {
+                        labels[j] = id;
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+        components.push(component);
+    }
+    Labels { labels, components }
+}
+
+#[derive(Debug, Clone)]
+pub struct IslandOptions {
+    /// Components with fewer voxels are removed.
+    pub min_size: usize,
+    /// Remove components which do not reach the ground layer.
+    pub require_ground: bool,
+    /// Y of the ground layer, defaults to the lowest layer containing any blocks.
+    pub ground_y: Option<usize>,
+    pub neighbourhood: Neighbourhood,
+}
+
+impl Default for IslandOptions {
+    fn default() -> Self {
+        Self {
+            min_size: 1,
+            require_ground: true,
+            ground_y: None,
+            neighbourhood: Neighbourhood::TwentySix,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct IslandReport {
+    pub removed: Vec<Component>,
+    pub removed_voxels: usize,
+    pub kept_components: usize,
+}
+
+/// Removes floating debris: components smaller than `min_size`, and if `require_ground` is set,
+/// components not touching the ground layer.
+pub fn remove_islands(arr: &mut ArrayModel, opts: &IslandOptions) -> IslandReport {
+    let labels = label_components(arr, Match::Occupancy, opts.neighbourhood);
+    let ground_y = opts.ground_y.or_else(|| labels.components.iter().map(|c| c.min.1).min());
+
+    let mut report = IslandReport::default();
+    let mut remove = vec![false; labels.components.len() + 1];
+    for component in labels.components.into_iter() {
+        let grounded = ground_y.is_some_and(|y| component.min.1 <= y && y <= component.max.1);
+        if component.size < opts.min_size || (opts.require_ground && !grounded) {
+            remove[component.id as usize] = true;
+            report.removed_voxels += component.size;
+            report.removed.push(component);
+        } else {
+            report.kept_components += 1;
+        }
+    }
+
+    for (b, &label) in arr.blocks.iter_mut().zip(labels.labels.iter()) {
+        if remove[label as usize] {
+            *b = 0;
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(blocks: &[Block]) -> ArrayModel {
+        let mut arr = ArrayModel::new((1, 1, blocks.len() as _), 1.0);
+        arr.blocks.copy_from_slice(blocks);
+        arr
+    }
+
+    #[test]
+    fn flood_fill_by_block() {
+        let mut arr = row(&[3, 3, 5, 3]);
+        assert_eq!(flood_fill(&mut arr, (0, 0, 0), 7, Match::Block, Neighbourhood::Six), 2);
+        assert_eq!(arr.blocks, [7, 7, 5, 3]);
+        assert_eq!(flood_fill(&mut arr, (0, 0, 0), 7, Match::Block, Neighbourhood::Six), 0);
+    }
+
+    #[test]
+    fn flood_fill_by_occupancy_with_start_block() {
+        let mut arr = row(&[3, 5, 0]);
+        assert_eq!(flood_fill(&mut arr, (0, 0, 0), 3, Match::Occupancy, Neighbourhood::Six), 1);
+        assert_eq!(arr.blocks, [3, 3, 0]);
+    }
+
+    #[test]
+    fn labels_and_enclosed_air() {
+        let mut arr = ArrayModel::new((3, 3, 3), 1.0);
+        arr.blocks.fill(1);
+        arr.set((1, 1, 1), 0);
+        assert_eq!(label_components(&arr, Match::Occupancy, Neighbourhood::Six).components.len(), 1);
+        let enclosed = enclosed_air(&arr, Neighbourhood::Six);
+        assert_eq!(enclosed.len(), 1);
+        assert_eq!((enclosed[0].size, enclosed[0].min), (1, (1, 1, 1)));
+    }
+
+    #[test]
+    fn removes_floating_islands() {
+        let mut arr = ArrayModel::new((5, 5, 5), 1.0);
+        arr.set((0, 0, 0), 1);
+        arr.set((0, 1, 0), 1);
+        arr.set((3, 3, 3), 2);
+        let report = remove_islands(&mut arr, &IslandOptions::default());
+        assert_eq!((report.removed_voxels, report.kept_components), (1, 1));
+        assert_eq!(arr.get((3, 3, 3)), Some(0));
+        assert_eq!(arr.iter_filled().count(), 2);
+        let removed = &report.removed[0];
+        assert_eq!((removed.size, removed.min, removed.max, removed.block), (1, (3, 3, 3), (3, 3, 3), 2));
+    }
+
+    #[test]
+    fn removes_small_islands() {
+        let mut arr = ArrayModel::new((5, 5, 5), 1.0);
+        arr.set((0, 0, 0), 1);
+        arr.set((0, 1, 0), 1);
+        arr.set((0, 2, 0), 1);
+        arr.set((4, 0, 4), 2);
+        arr.set((4, 0, 3), 2);
+        let opts = IslandOptions { min_size: 3, require_ground: false, ..Default::default() };
+        let report = remove_islands(&mut arr, &opts);
+        assert_eq!((report.removed_voxels, report.kept_components), (2, 1));
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!((report.removed[0].min, report.removed[0].max), ((4, 0, 3), (4, 0, 4)));
+        assert_eq!(arr.iter_filled().count(), 3);
+
+        // A component of exactly `min_size` voxels is kept.
+        let report = remove_islands(&mut arr, &opts);
+        assert_eq!((report.removed_voxels, report.kept_components), (0, 1));
+    }
+
+    #[test]
+    fn explicit_ground_layer() {
+        let mut arr = ArrayModel::new((5, 5, 5), 1.0);
+        // Resting on y = 1, e.g. on a platform which is not part of the grid.
+        arr.set((0, 1, 0), 1);
+        arr.set((0, 2, 0), 1);
+        arr.set((3, 0, 3), 2);
+        let opts = IslandOptions { ground_y: Some(1), ..Default::default() };
+        let report = remove_islands(&mut arr, &opts);
+        assert_eq!((report.removed_voxels, report.kept_components), (1, 1));
+        assert_eq!(report.removed[0].block, 2);
+        assert_eq!(arr.get((3, 0, 3)), Some(0));
+        assert_eq!(arr.get((0, 1, 0)), Some(1));
+    }
+}
@@ -2,6 +2,7 @@ use std::ops::Mul;
 use crate::vec3::Vec3;
 use super::float;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Axis {
     X,
     Y,
@@ -31,6 +32,7 @@ impl Order {
 }
 
 
+#[derive(Default)]
 pub struct RotMtx(pub [[f32; 3]; 3]);
 
 impl RotMtx {
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use crate::error::{Error, Result};
+use crate::grid::offset_coord;
+use crate::model2arr::{uint, ArrayModel, Block};
+use crate::registry::BlockRegistry;
+use crate::vec3::Vec3;
+use super::float;
+
+/// Which block wins where both grids are filled in a union.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnionPolicy {
+    KeepLeft,
+    KeepRight,
+    /// Blocks earlier in the list win, unlisted blocks lose to listed ones and keep left otherwise.
+    /// Ids are those of the left grid's registry, which the right grid's blocks are mapped to.
+    Priority(Vec<Block>),
+}
+
+impl UnionPolicy {
+    fn resolve(&self, left: Block, right: Block) -> Block {
+        match self {
+            Self::KeepLeft => left,
+            Self::KeepRight => right,
+            Self::Priority(order) => {
+                let rank = |b: Block| order.iter().position(|&p| p == b).unwrap_or(usize::MAX);
+                if rank(right) < rank(left) { right } else { left }
+            }
+        }
+    }
+}
+
+/// Voxels filled in either grid. The result grows to the bounding box of both grids, so no part
+/// of `b` is lost.
+pub fn union(a: &ArrayModel, b: &ArrayModel, offset: (i64, i64, i64), policy: &UnionPolicy) -> Result<ArrayModel> {
+    combine(a, b, offset, true, |l, r| match (l, r) {
+        (0, r) => r,
+        (l, 0) => l,
+        (l, r) => policy.resolve(l, r),
+    })
+}
+
+/// Voxels filled in both grids, keeping the blocks of `a`.
+pub fn intersection(a: &ArrayModel, b: &ArrayModel, offset: (i64, i64, i64)) -> ArrayModel {
+    combine(a, b, offset, false, |l, r| if r != 0 { l } else { 0 }).expect("Result has the dims of a")
+}
+
+/// Voxels of `a` which are not filled in `b`, e.g. cutting a doorway out of a wall.
+pub fn difference(a: &ArrayModel, b: &ArrayModel, offset: (i64, i64, i64)) -> ArrayModel {
+    combine(a, b, offset, false, |l, r| if r != 0 { 0 } else { l }).expect("Result has the dims of a")
+}
+
+/// Voxels filled in exactly one of the grids. Grows like `union`.
+pub fn xor(a: &ArrayModel, b: &ArrayModel, offset: (i64, i64, i64)) -> Result<ArrayModel> {
+    combine(a, b, offset, true, |l, r| match (l, r) {
+        (0, r) => r,
+        (l, 0) => l,
+        _ => 0,
+    })
+}
+
+/// Combines `b`, with its voxel (0, 0, 0) at `offset` in `a`, with `a`. The result has the transform
+/// of `a`, and its dims unless `grow` is set, otherwise parts of `b` outside of `a` are clipped.
+///
+/// Only a growing combine (union, xor) takes blocks from `b`, intersection and difference only
+/// check them for air. Those blocks are mapped to `a`'s registry by their definition, blocks `a`
+/// does not define are added to it, under a new id if `a` uses theirs for a different block.
+fn combine<F>(a: &ArrayModel, b: &ArrayModel, offset: (i64, i64, i64), grow: bool, f: F) -> Result<ArrayModel>
+where
+        F: Fn(Block, Block) -> Block, {
+    let (lo, hi) = match grow {
+        true => (
+            (offset.0.min(0), offset.1.min(0), offset.2.min(0)),
+            (
+                (a.dims.0 as i64).max(offset.0 + b.dims.0 as i64),
+                (a.dims.1 as i64).max(offset.1 + b.dims.1 as i64),
+                (a.dims.2 as i64).max(offset.2 + b.dims.2 as i64),
+            ),
+        ),
+        false => ((0, 0, 0), (a.dims.0 as i64, a.dims.1 as i64, a.dims.2 as i64)),
+    };
+    let size = |lo: i64, hi: i64| uint::try_from(hi - lo).map_err(|_| Error::DimsTooLarge);
+    let dims = (size(lo.0, hi.0)?, size(lo.1, hi.1)?, size(lo.2, hi.2)?);
+    let mut transform = a.transform.clone();
+    transform.origin = transform.origin + transform.voxel_size.clone() * Vec3::new(lo.0 as float, lo.1 as float, lo.2 as float);
+
+    let mut out = ArrayModel::with_transform(dims, transform, a.resolution);
+    out.registry = a.registry.clone();
+    out.paste(a, (-lo.0, -lo.1, -lo.2), false);
+    let remap = match grow {
+        true => merge_registry(&mut out.registry, &b.registry),
+        false => HashMap::new(),
+    };
+
+    let back = (lo.0 - offset.0, lo.1 - offset.1, lo.2 - offset.2);
+    for i in 0..out.blocks.len() {
+        let r = offset_coord(out.coord(i), back, b.dims).map_or(0, |c| b.get(c).unwrap_or(0));
+        out.blocks[i] = f(out.blocks[i], remap.get(&r).copied().unwrap_or(r));
+    }
+    Ok(out)
+}
+
+/// Adds the definitions of `other` to `registry`, returning the new id of every block of `other`
+/// whose id changes.
+fn merge_registry(registry: &mut BlockRegistry, other: &BlockRegistry) -> HashMap<Block, Block> {
+    let mut remap = HashMap::new();
+    for (id, def) in other.iter() {
+        if registry.get(id) == Some(def) {
+            continue;
+        }
+        let existing = registry.iter().find(|(_, d)| *d == def).map(|(existing, _)| existing);
+        let new_id = match existing {
+            Some(existing) => existing,
+            None if registry.get(id).is_none() => {
+                registry.insert(id, def.clone());
+                id
+            }
+            None => registry.register(def.clone()),
+        };
+        if new_id != id {
+            remap.insert(id, new_id);
+        }
+    }
+    remap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::BlockDef;
+
+    fn cube(n: u16, b: Block) -> ArrayModel {
+        let mut arr = ArrayModel::new((n as _, n as _, n as _), 1.0);
+        arr.blocks.fill(b);
+        arr
+    }
+
+    #[test]
+    fn union_grows_to_both_grids() {
+        let a = ArrayModel::new((2, 2, 2), 1.0);
+        let b = cube(2, 1);
+        let u = union(&a, &b, (1, 1, 1), &UnionPolicy::KeepLeft).unwrap();
+        assert_eq!(u.dims, (3, 3, 3));
+        assert_eq!(u.iter_filled().count(), 8);
+        assert_eq!(u.get((2, 2, 2)), Some(1));
+
+        // Negative offsets move the origin.
+        let u = union(&cube(2, 1), &cube(2, 1), (-1, 0, 0), &UnionPolicy::KeepLeft).unwrap();
+        assert_eq!(u.dims, (3, 2, 2));
+        assert_eq!(u.iter_filled().count(), 12);
+        assert_eq!(u.transform.origin.x, -1.0);
+    }
+
+    #[test]
+    fn xor_grows_and_removes_overlap() {
+        let x = xor(&cube(2, 1), &cube(2, 1), (1, 0, 0)).unwrap();
+        assert_eq!(x.dims, (3, 2, 2));
+        assert_eq!(x.iter_filled().count(), 8);
+        assert_eq!(x.get((1, 0, 0)), Some(0));
+    }
+
+    #[test]
+    fn intersection_and_difference_keep_dims() {
+        let i = intersection(&cube(3, 1), &cube(3, 2), (2, 2, 2));
+        assert_eq!((i.dims, i.iter_filled().count()), ((3, 3, 3), 1));
+        assert_eq!(i.get((2, 2, 2)), Some(1));
+        let d = difference(&cube(3, 1), &cube(3, 2), (2, 2, 2));
+        assert_eq!((d.dims, d.iter_filled().count()), ((3, 3, 3), 26));
+    }
+
+    #[test]
+    fn intersection_and_difference_keep_left_registry() {
+        let mut a = cube(2, 1);
+        a.registry.insert(1, BlockDef::new("dirt"));
+        let mut b = cube(2, 1);
+        b.registry.insert(1, BlockDef::new("glass"));
+        b.registry.insert(2, BlockDef::new("stone"));
+        for out in [intersection(&a, &b, (1, 0, 0)), difference(&a, &b, (1, 0, 0))] {
+            assert_eq!(out.registry, a.registry);
+            assert!(out.iter_filled().all(|(_, block)| block == 1));
+        }
+    }
+
+    #[test]
+    fn right_blocks_are_remapped_by_definition() {
+        let mut a = cube(1, 1);
+        a.registry.insert(1, BlockDef::new("dirt"));
+        a.registry.insert(2, BlockDef::new("stone"));
+        let mut b = cube(1, 1);
+        b.registry.insert(1, BlockDef::new("stone"));
+        let mut c = cube(1, 1);
+        c.registry.insert(1, BlockDef::new("glass"));
+
+        let u = union(&a, &b, (1, 0, 0), &UnionPolicy::KeepLeft).unwrap();
+        assert_eq!(u.get((0, 0, 0)), Some(1));
+        assert_eq!(u.get((1, 0, 0)), Some(2));
+
+        let u = union(&a, &c, (1, 0, 0), &UnionPolicy::KeepLeft).unwrap();
+        let glass = u.registry.id_of("glass").unwrap();
+        assert_ne!(glass, 1);
+        assert_eq!(u.get((1, 0, 0)), Some(glass));
+        assert_eq!(u.registry.name(1), "dirt");
+    }
+
+    #[test]
+    fn priority_policy() {
+        let u = union(&cube(1, 1), &cube(1, 2), (0, 0, 0), &UnionPolicy::Priority(vec![2])).unwrap();
+        assert_eq!(u.get((0, 0, 0)), Some(2));
+        let u = union(&cube(1, 1), &cube(1, 2), (0, 0, 0), &UnionPolicy::KeepLeft).unwrap();
+        assert_eq!(u.get((0, 0, 0)), Some(1));
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt::Write;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::grid::VoxelGrid;
+use crate::model2arr::{ArrayModel, Block, CoordXYZ};
+use crate::registry::{BlockRegistry, AIR};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoxelChange {
+    /// c_xyz: (x, y, z)
+    pub c_xyz: (usize, usize, usize),
+    pub old: Block,
+    pub new: Block,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Diff {
+    /// Air which became a block.
+    pub added: Vec<VoxelChange>,
+    /// Blocks which became air.
+    pub removed: Vec<VoxelChange>,
+    /// Blocks which became a different block.
+    pub changed: Vec<VoxelChange>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+
+    pub fn to_patch(&self) -> Patch {
+        let mut changes = self.added
+            .iter()
+            .chain(self.removed.iter())
+            .chain(self.changed.iter())
+            .collect::<Vec<&VoxelChange>>();
+        changes.sort_by_key(|c| (c.c_xyz.1, c.c_xyz.0, c.c_xyz.2));
+
+        let mut runs: Vec<PatchRun> = Vec::new();
+        for c in changes {
+            let (x, y, z) = c.c_xyz;
+            match runs.last_mut() {
+                Some(run) if run.p[0] == x && run.p[1] == y && run.p[2] + run.b.len() == z => run.b.push(c.new),
+                _ => runs.push(PatchRun { p: [x, y, z], b: vec![c.new] }),
+            }
+        }
+        Patch { runs }
+    }
+}
+
+/// Changed voxels along Z, starting at `p` = [x, y, z].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PatchRun {
+    pub p: [usize; 3],
+    /// New blocks
+    pub b: Vec<Block>,
+}
+
+/// Compact, serializable set of changes which turns one grid into another.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub runs: Vec<PatchRun>,
+}
+
+/// Voxel differences from `old` to `new`. Grids of different dims are compared over the larger
+/// extent, with voxels outside of a grid counting as air.
+///
+/// Diffing against an empty grid lists every block of `new` as added.
+pub fn diff(old: &ArrayModel, new: &ArrayModel) -> Diff {
+    let dims: CoordXYZ = (old.dims.0.max(new.dims.0), old.dims.1.max(new.dims.1), old.dims.2.max(new.dims.2));
+    let mut diff = Diff::default();
+    for y in 0..dims.1 as usize {
+        for x in 0..dims.0 as usize {
+            for z in 0..dims.2 as usize {
+                let c_xyz = (x, y, z);
+                let (o, n) = (old.get(c_xyz).unwrap_or(AIR), new.get(c_xyz).unwrap_or(AIR));
+                let change = VoxelChange { c_xyz, old: o, new: n };
+                match (o, n) {
+                    (o, n) if o == n => {}
+                    (AIR, _) => diff.added.push(change),
+                    (_, AIR) => diff.removed.push(change),
+                    _ => diff.changed.push(change),
+                }
+            }
+        }
+    }
+    diff
+}
+
+/// Applies the patch in place, returns the number of voxels written. Nothing is written if any
+/// run lies outside of the grid.
+pub fn apply_patch(arr: &mut ArrayModel, patch: &Patch) -> Result<usize> {
+    for run in patch.runs.iter() {
+        let [x, y, z] = run.p;
+        if let Some(len) = run.b.len().checked_sub(1) {
+            let end = z.checked_add(len).ok_or(Error::OutOfBounds { c_xyz: (x, y, z), dims: arr.dims })?;
+            for c_xyz in [(x, y, z), (x, y, end)] {
+                if !arr.in_bounds(c_xyz) {
+                    return Err(Error::OutOfBounds { c_xyz, dims: arr.dims });
+                }
+            }
+        }
+    }
+
+    let mut written = 0;
+    for run in patch.runs.iter() {
+        let [x, y, z] = run.p;
+        for (i, &b) in run.b.iter().enumerate() {
+            arr.try_set((x, y, z + i), b)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+impl Patch {
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|r| r.b.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.iter().all(|r| r.b.is_empty())
+    }
+
+    /// Minecraft function which applies the patch in game, with grid voxel (0, 0, 0) at `origin`.
+    ///
+    /// Runs of the same block become one `fill` command. Every block needs a `minecraft_state` in
+    /// the registry, air is always `minecraft:air`.
+    pub fn to_mcfunction(&self, registry: &BlockRegistry, origin: (i64, i64, i64)) -> Result<String> {
+        let mut out = String::new();
+        for run in self.runs.iter() {
+            let (x, y) = (origin.0 + run.p[0] as i64, origin.1 + run.p[1] as i64);
+            let mut start = 0;
+            while start < run.b.len() {
+                let block = run.b[start];
+                let len = run.b[start..].iter().take_while(|&&b| b == block).count();
+                let state = match block {
+                    AIR => "minecraft:air",
+                    _ => registry
+                        .get(block)
+                        .and_then(|def| def.minecraft_state.as_deref())
+                        .ok_or(Error::MissingBlockState(block))?,
+                };
+                let z = origin.2 + (run.p[2] + start) as i64;
+                if len == 1 {
+                    writeln!(out, "setblock {} {} {} {}", x, y, z, state).unwrap();
+                } else {
+                    writeln!(out, "fill {} {} {} {} {} {} {}", x, y, z, x, y, z + len as i64 - 1, state).unwrap();
+                }
+                start += len;
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::BlockDef;
+
+    fn grid(n: u16, voxels: &[((usize, usize, usize), Block)]) -> ArrayModel {
+        let mut arr = ArrayModel::new((n as _, n as _, n as _), 1.0);
+        for &(c_xyz, b) in voxels {
+            arr.set(c_xyz, b);
+        }
+        arr
+    }
+
+    #[test]
+    fn diff_classifies_changes() {
+        let old = grid(3, &[((0, 0, 0), 1), ((1, 1, 1), 2)]);
+        let new = grid(3, &[((1, 1, 1), 3), ((2, 2, 0), 1), ((2, 2, 1), 1)]);
+        let d = diff(&old, &new);
+        assert_eq!((d.added.len(), d.removed.len(), d.changed.len()), (2, 1, 1));
+        assert_eq!(d.changed[0], VoxelChange { c_xyz: (1, 1, 1), old: 2, new: 3 });
+        assert_eq!(d.to_patch().runs.len(), 3);
+    }
+
+    #[test]
+    fn patch_round_trip() {
+        let old = grid(4, &[((0, 0, 0), 1), ((1, 2, 3), 2), ((3, 3, 3), 4)]);
+        let new = grid(4, &[((1, 2, 3), 5), ((2, 0, 0), 1), ((2, 0, 1), 1), ((2, 0, 2), 6)]);
+        let patch = diff(&old, &new).to_patch();
+        let json = serde_json::to_string(&patch).unwrap();
+        let patch: Patch = serde_json::from_str(&json).unwrap();
+
+        let mut patched = old.clone();
+        assert_eq!(apply_patch(&mut patched, &patch).unwrap(), patch.len());
+        assert_eq!(patched.blocks, new.blocks);
+    }
+
+    #[test]
+    fn out_of_bounds_patch_leaves_grid_unchanged() {
+        let old = grid(2, &[((0, 0, 0), 1)]);
+        let new = grid(3, &[((0, 0, 0), 2), ((2, 2, 2), 1)]);
+        let mut patched = old.clone();
+        let result = apply_patch(&mut patched, &diff(&old, &new).to_patch());
+        assert!(matches!(result, Err(Error::OutOfBounds { .. })));
+        assert_eq!(patched.blocks, old.blocks);
+
+        let overflowing = Patch { runs: vec![PatchRun { p: [0, 0, usize::MAX], b: vec![1, 1] }] };
+        assert!(matches!(apply_patch(&mut patched, &overflowing), Err(Error::OutOfBounds { .. })));
+        assert_eq!(patched.blocks, old.blocks);
+    }
+
+    #[test]
+    fn mcfunction_merges_runs() {
+        let mut new = grid(3, &[((1, 0, 0), 1), ((1, 0, 1), 1), ((1, 0, 2), 1)]);
+        let patch = diff(&grid(3, &[((0, 0, 0), 1)]), &new).to_patch();
+        assert!(matches!(patch.to_mcfunction(&new.registry, (0, 0, 0)), Err(Error::MissingBlockState(1))));
+        new.registry.insert(1, BlockDef { minecraft_state: Some("minecraft:stone".to_string()), ..BlockDef::new("stone") });
+        let out = patch.to_mcfunction(&new.registry, (10, 64, 0)).unwrap();
+        assert_eq!(out, "setblock 10 64 0 minecraft:air\nfill 11 64 0 11 64 2 minecraft:stone\n");
+    }
+}
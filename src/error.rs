@@ -0,0 +1,81 @@
+use std::fmt;
+use crate::model2arr::{Block, CoordXYZ};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Json(serde_json::Error),
+    /// A file name that is not valid UTF-8.
+    InvalidFileName(std::ffi::OsString),
+    /// A texture listed in `TextureNames` which is not in the textures directory.
+    MissingTexture(String),
+    /// The model has no vertices, so it has no bounds.
+    EmptyModel,
+    /// A face references a vertex index that does not exist.
+    InvalidFace { face: usize, index: usize },
+    /// Nested blocks which are ragged or larger than `uint::MAX` along an axis.
+    InvalidNestedArray,
+    /// Fitting a model would scale it to nothing, e.g. the padding leaves no room in the grid.
+    NoRoomToFit,
+    /// A block without a Minecraft block state in the registry.
+    MissingBlockState(Block),
+    /// Resulting dims would be larger than `uint::MAX` along an axis.
+    DimsTooLarge,
+    /// c_xyz: (x, y, z)
+    OutOfBounds { c_xyz: (usize, usize, usize), dims: CoordXYZ },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Image(e) => write!(f, "image error: {}", e),
+            Self::Json(e) => write!(f, "json error: {}", e),
+            Self::InvalidFileName(name) => write!(f, "file name is not valid utf-8: {:?}", name),
+            Self::MissingTexture(name) => write!(f, "texture {} does not exist", name),
+            Self::EmptyModel => write!(f, "model has no vertices"),
+            Self::InvalidFace { face, index } => {
+                write!(f, "face {} references missing vertex {}", face, index)
+            }
+            Self::InvalidNestedArray => write!(f, "nested blocks are ragged or too large"),
+            Self::NoRoomToFit => write!(f, "no room to fit the model in the grid"),
+            Self::MissingBlockState(block) => write!(f, "block {} has no minecraft block state", block),
+            Self::DimsTooLarge => write!(f, "dims are too large"),
+            Self::OutOfBounds { c_xyz, dims } => {
+                write!(f, "coordinate {:?} is outside of grid with dims {:?}", c_xyz, dims)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Image(e) => Some(e),
+            Self::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
@@ -0,0 +1,203 @@
+use crate::error::{Error, Result};
+use crate::grid::GridTransform;
+use crate::model::Model;
+use crate::model2arr::CoordXYZ;
+use crate::vec3::Vec3;
+use super::float;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scaling {
+    /// Keep the aspect ratio, the largest axis decides the scale.
+    Uniform,
+    /// Stretch every axis to fill the grid.
+    NonUniform,
+}
+
+#[derive(Debug, Clone)]
+pub struct FitOptions {
+    pub scaling: Scaling,
+    /// Empty voxels left on every side of the grid.
+    pub padding: float,
+    /// Center the model on X and Z, otherwise it is aligned to the low side.
+    pub center_xz: bool,
+    /// Place the bottom of the model on the lowest layer, otherwise it is centered on Y.
+    pub ground_y: bool,
+    /// Scale uniformly so the model is this many blocks tall, ignoring `scaling`. Must be positive,
+    /// heights below 2 flatten the model into a single layer.
+    /// Parts of the model which then do not fit in the grid are clipped when voxelizing.
+    pub height_blocks: Option<float>,
+}
+
+impl Default for FitOptions {
+    fn default() -> Self {
+        Self {
+            scaling: Scaling::Uniform,
+            padding: 0.0,
+            center_xz: false,
+            ground_y: true,
+            height_blocks: None,
+        }
+    }
+}
+
+/// Bounding box shared by all models.
+pub fn models_dims(models: &[Model]) -> Result<(Vec3, Vec3)> {
+    let mut bounds: Option<(Vec3, Vec3)> = None;
+    for model in models.iter().filter(|m| !m.vertices.0.is_empty()) {
+        let (min, max) = model.try_model_dims()?;
+        bounds = Some(match bounds {
+            None => (min, max),
+            Some((b_min, b_max)) => (
+                Vec3::new(b_min.x.min(min.x), b_min.y.min(min.y), b_min.z.min(min.z)),
+                Vec3::new(b_max.x.max(max.x), b_max.y.max(max.y), b_max.z.max(max.z)),
+            ),
+        });
+    }
+    bounds.ok_or(Error::EmptyModel)
+}
+
+/// Transform which places the model space bounding box `bounds` into a grid of `dims`.
+///
+/// Voxelizing with this transform gives the same result as `fit_to_grid` followed by voxelizing
+/// with the default transform, without modifying the model.
+pub fn fit_transform(bounds: (Vec3, Vec3), dims: CoordXYZ, opts: &FitOptions) -> Result<GridTransform> {
+    let (scale, offset) = fit_scale_offset(&bounds, dims, opts)?;
+    Ok(GridTransform::new(
+        bounds.0 - offset / scale.clone(),
+        Vec3::ONE / scale,
+    ))
+}
+
+/// Moves and scales the model to fit in `dims`.
+///
+/// Returns the transform from the resulting grid back to the original model space.
+pub fn fit_to_grid(model: &mut Model, dims: CoordXYZ, opts: &FitOptions) -> Result<GridTransform> {
+    fit_models_to_grid(std::slice::from_mut(model), dims, opts)
+}
+
+/// Moves and scales all models by the same amount so their shared bounding box fits in `dims`.
+///
+/// Returns the transform from the resulting grid back to the original model space.
+pub fn fit_models_to_grid(models: &mut [Model], dims: CoordXYZ, opts: &FitOptions) -> Result<GridTransform> {
+    let bounds = models_dims(models)?;
+    let (scale, offset) = fit_scale_offset(&bounds, dims, opts)?;
+
+    for model in models.iter_mut() {
+        model.mv(bounds.0.clone() * -1.0);
+        model.scale(scale.clone());
+        model.mv(offset.clone());
+    }
+
+    Ok(GridTransform::new(
+        bounds.0 - offset / scale.clone(),
+        Vec3::ONE / scale,
+    ))
+}
+
+fn fit_scale_offset(bounds: &(Vec3, Vec3), dims: CoordXYZ, opts: &FitOptions) -> Result<(Vec3, Vec3)> {
+    // A vertex at `dims - 1` still lands in the last voxel.
+    let available = Vec3::new(dims.0 as float, dims.1 as float, dims.2 as float) - 1.0 - opts.padding * 2.0;
+    let extent = bounds.1.clone() - bounds.0.clone();
+    if available.x < 0.0 || available.y < 0.0 || available.z < 0.0 {
+        return Err(Error::NoRoomToFit);
+    }
+
+    let axis_scale = |available: float, extent: float| {
+        if extent > 0.0 { (available / extent).max(0.0) } else { float::INFINITY }
+    };
+    let per_axis = Vec3::new(
+        axis_scale(available.x, extent.x),
+        axis_scale(available.y, extent.y),
+        axis_scale(available.z, extent.z),
+    );
+    let mut uniform = per_axis.min_val();
+    if !uniform.is_finite() {
+        // Every axis is flat, any scale works.
+        uniform = 1.0;
+    }
+
+    let scale = match (opts.height_blocks, opts.scaling) {
+        (Some(height), _) => {
+            if height.is_nan() || height <= 0.0 {
+                return Err(Error::NoRoomToFit);
+            }
+            // Like `available`, `height` layers span `height - 1`. Below 2 layers that would
+            // flatten the model to nothing, keep it inside one layer instead.
+            let s = axis_scale((height - 1.0).max(height / 2.0), extent.y);
+            Vec3::from_scalar(if s.is_finite() { s } else { uniform })
+        }
+        (None, Scaling::Uniform) => Vec3::from_scalar(uniform),
+        (None, Scaling::NonUniform) => {
+            let or_uniform = |s: float| if s.is_finite() { s } else { uniform };
+            Vec3::new(or_uniform(per_axis.x), or_uniform(per_axis.y), or_uniform(per_axis.z))
+        }
+    };
+
+    // Scaling an axis with any extent to nothing collapses the model.
+    let collapsed = |s: float, extent: float| extent > 0.0 && (s <= 0.0 || !s.is_finite());
+    if collapsed(scale.x, extent.x) || collapsed(scale.y, extent.y) || collapsed(scale.z, extent.z) {
+        return Err(Error::NoRoomToFit);
+    }
+
+    let scaled = extent * scale.clone();
+    let center = |available: float, scaled: float| opts.padding + (available - scaled) / 2.0;
+    let offset = Vec3::new(
+        if opts.center_xz { center(available.x, scaled.x) } else { opts.padding },
+        if opts.ground_y { opts.padding } else { center(available.y, scaled.y) },
+        if opts.center_xz { center(available.z, scaled.z) } else { opts.padding },
+    );
+
+    Ok((scale, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Faces, Points};
+
+    fn tetrahedron() -> Model {
+        Model::new(
+            Points(vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(4.0, 0.0, 0.0),
+                Vec3::new(0.0, 4.0, 0.0),
+                Vec3::new(0.0, 0.0, 4.0),
+            ]),
+            Faces(vec![[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]]),
+        )
+    }
+
+    #[test]
+    fn fits_into_grid() {
+        let mut model = tetrahedron();
+        let transform = fit_to_grid(&mut model, (9, 9, 9), &FitOptions::default()).unwrap();
+        let (min, max) = model.model_dims();
+        assert_eq!([min.x, min.y, min.z, max.x, max.y, max.z], [0.0, 0.0, 0.0, 8.0, 8.0, 8.0]);
+        let size = transform.voxel_size;
+        assert_eq!([size.x, size.y, size.z], [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn one_block_tall_is_one_layer() {
+        let opts = FitOptions { height_blocks: Some(1.0), ..Default::default() };
+        let mut model = tetrahedron();
+        let transform = fit_to_grid(&mut model, (8, 8, 8), &opts).unwrap();
+        assert!(transform.voxel_size.x.is_finite() && transform.origin.x.is_finite());
+        let (_, max) = model.model_dims();
+        assert!(max.y > 0.0 && max.y < 1.0);
+    }
+
+    #[test]
+    fn no_room_is_an_error() {
+        let padded = FitOptions { padding: 5.0, ..Default::default() };
+        assert!(matches!(fit_to_grid(&mut tetrahedron(), (8, 8, 8), &padded), Err(Error::NoRoomToFit)));
+        // Padding leaving exactly one voxel collapses the model to a point.
+        let padded = FitOptions { padding: 3.5, ..Default::default() };
+        assert!(matches!(fit_to_grid(&mut tetrahedron(), (8, 8, 8), &padded), Err(Error::NoRoomToFit)));
+        for height in [0.0, -2.0, float::NAN] {
+            let opts = FitOptions { height_blocks: Some(height), ..Default::default() };
+            let bounds = models_dims(&[tetrahedron()]).unwrap();
+            assert!(matches!(fit_transform(bounds, (8, 8, 8), &opts), Err(Error::NoRoomToFit)));
+        }
+    }
+}
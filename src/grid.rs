@@ -0,0 +1,158 @@
+use crate::error::{Error, Result};
+use crate::model2arr::{voxel_index, Block, CoordXYZ};
+use crate::registry::BlockRegistry;
+use crate::vec3::Vec3;
+use super::float;
+
+/// Maps voxel coordinates to world (model) space.
+///
+/// Voxel (x, y, z) covers `origin + (x, y, z) * voxel_size` up to `origin + (x + 1, y + 1, z + 1) * voxel_size`.
+#[derive(Debug, Clone)]
+pub struct GridTransform {
+    pub origin: Vec3,
+    pub voxel_size: Vec3,
+}
+
+impl Default for GridTransform {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, Vec3::ONE)
+    }
+}
+
+impl GridTransform {
+    pub fn new(origin: Vec3, voxel_size: Vec3) -> Self {
+        Self { origin, voxel_size }
+    }
+
+    pub fn uniform(origin: Vec3, voxel_size: float) -> Self {
+        Self::new(origin, Vec3::from_scalar(voxel_size))
+    }
+
+    /// Continuous grid coordinates of a world position, i.e. `(p - origin) / voxel_size`.
+    pub fn world_to_voxel(&self, p: &Vec3) -> Vec3 {
+        (p.clone() - self.origin.clone()) / self.voxel_size.clone()
+    }
+
+    /// Voxel containing a world position, or `None` if it lies outside of `dims`.
+    pub fn world_to_voxel_coord(&self, p: &Vec3, dims: CoordXYZ) -> Option<(usize, usize, usize)> {
+        let v = self.world_to_voxel(p);
+        Some((voxel_index(v.x, dims.0)?, voxel_index(v.y, dims.1)?, voxel_index(v.z, dims.2)?))
+    }
+
+    /// World position of the minimum corner of a voxel.
+    /// c_xyz: (x, y, z)
+    pub fn voxel_to_world(&self, c_xyz: (usize, usize, usize)) -> Vec3 {
+        let v = Vec3::new(c_xyz.0 as float, c_xyz.1 as float, c_xyz.2 as float);
+        v * self.voxel_size.clone() + self.origin.clone()
+    }
+
+    /// World position of the centre of a voxel.
+    /// c_xyz: (x, y, z)
+    pub fn voxel_center(&self, c_xyz: (usize, usize, usize)) -> Vec3 {
+        self.voxel_to_world(c_xyz) + self.voxel_size.clone() * 0.5
+    }
+}
+
+/// Which voxels count as neighbours: sharing a face, a face or an edge, or any corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood {
+    Six,
+    Eighteen,
+    TwentySix,
+}
+
+impl Neighbourhood {
+    /// Offsets of the neighbours, closest (face neighbours) first.
+    pub fn offsets(&self) -> Vec<(i64, i64, i64)> {
+        let max_dist = match self {
+            Self::Six => 1,
+            Self::Eighteen => 2,
+            Self::TwentySix => 3,
+        };
+        let mut offsets = Vec::with_capacity(26);
+        for dist in 1..=max_dist {
+            for dx in -1..=1i64 {
+                for dy in -1..=1i64 {
+                    for dz in -1..=1i64 {
+                        if dx.abs() + dy.abs() + dz.abs() == dist {
+                            offsets.push((dx, dy, dz));
+                        }
+                    }
+                }
+            }
+        }
+        offsets
+    }
+}
+
+/// Coordinate offset by `d`, or `None` if it lies outside of `dims`.
+pub fn offset_coord(c_xyz: (usize, usize, usize), d: (i64, i64, i64), dims: CoordXYZ) -> Option<(usize, usize, usize)> {
+    let c = (c_xyz.0 as i64 + d.0, c_xyz.1 as i64 + d.1, c_xyz.2 as i64 + d.2);
+    let inside = c.0 >= 0 && c.1 >= 0 && c.2 >= 0
+        && c.0 < dims.0 as i64 && c.1 < dims.1 as i64 && c.2 < dims.2 as i64;
+    inside.then_some((c.0 as usize, c.1 as usize, c.2 as usize))
+}
+
+/// Common interface of the voxel stores (`ArrayModel`, `ChunkedModel`, `Octree`), so algorithms
+/// can be written once for all of them.
+pub trait VoxelGrid {
+    fn dims(&self) -> CoordXYZ;
+
+    fn resolution(&self) -> float;
+
+    fn transform(&self) -> &GridTransform;
+
+    fn registry(&self) -> &BlockRegistry;
+
+    /// c_xyz: (x, y, z)
+    fn get(&self, c_xyz: (usize, usize, usize)) -> Option<Block>;
+
+    /// Returns the previous block, or `None` if out of bounds.
+    /// c_xyz: (x, y, z)
+    fn set(&mut self, c_xyz: (usize, usize, usize), val: Block) -> Option<Block>;
+
+    /// All non air voxels with their coordinates, in no particular order.
+    fn iter_filled(&self) -> Box<dyn Iterator<Item = ((usize, usize, usize), Block)> + '_>;
+
+    fn in_bounds(&self, c_xyz: (usize, usize, usize)) -> bool {
+        let dims = self.dims();
+        c_xyz.0 < dims.0 as usize && c_xyz.1 < dims.1 as usize && c_xyz.2 < dims.2 as usize
+    }
+
+    /// c_xyz: (x, y, z)
+    fn try_get(&self, c_xyz: (usize, usize, usize)) -> Result<Block> {
+        self.get(c_xyz).ok_or(Error::OutOfBounds { c_xyz, dims: self.dims() })
+    }
+
+    /// c_xyz: (x, y, z)
+    fn try_set(&mut self, c_xyz: (usize, usize, usize), val: Block) -> Result<()> {
+        let dims = self.dims();
+        self.set(c_xyz, val).map(|_| ()).ok_or(Error::OutOfBounds { c_xyz, dims })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_round_trip() {
+        let t = GridTransform::new(Vec3::new(-1.0, 2.0, 0.0), Vec3::new(0.5, 1.0, 2.0));
+        let c = t.world_to_voxel_coord(&Vec3::new(0.2, 4.5, 3.9), (8, 8, 8));
+        assert_eq!(c, Some((2, 2, 1)));
+        let corner = t.voxel_to_world((2, 2, 1));
+        assert_eq!([corner.x, corner.y, corner.z], [0.0, 4.0, 2.0]);
+        assert_eq!(t.world_to_voxel_coord(&Vec3::new(-2.0, 2.0, 0.0), (8, 8, 8)), None);
+    }
+
+    #[test]
+    fn neighbourhoods() {
+        assert_eq!(Neighbourhood::Six.offsets().len(), 6);
+        assert_eq!(Neighbourhood::Eighteen.offsets().len(), 18);
+        let all = Neighbourhood::TwentySix.offsets();
+        assert_eq!(all.len(), 26);
+        assert_eq!(all[..6], Neighbourhood::Six.offsets()[..]);
+        assert_eq!(offset_coord((0, 0, 0), (-1, 0, 0), (2, 2, 2)), None);
+        assert_eq!(offset_coord((0, 1, 0), (1, 0, 1), (2, 2, 2)), Some((1, 1, 1)));
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+use image::{DynamicImage, Rgba, RgbaImage};
+use crate::error::Result;
+use crate::model2arr::{ArrayModel, Block};
+use crate::raster::{blend, Swatches};
+use crate::registry::AIR;
+
+#[derive(Debug, Clone)]
+pub struct GuideOptions {
+    /// Size of one voxel in pixels.
+    pub cell_size: u32,
+    /// Draw lines between cells, darker every 5 cells.
+    pub grid: bool,
+    /// Label the x (left to right) and z (top to bottom) coordinates along the edges, and the
+    /// layer's y in the top left corner.
+    pub labels: bool,
+    /// Outline blocks which differ from the block directly below them.
+    pub highlight_new: bool,
+    /// RGBA
+    pub highlight: [u8; 4],
+    /// RGBA
+    pub background: [u8; 4],
+}
+
+impl Default for GuideOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: 16,
+            grid: true,
+            labels: true,
+            highlight_new: true,
+            highlight: [255, 0, 0, 255],
+            background: [255, 255, 255, 255],
+        }
+    }
+}
+
+/// 3x5 pixel digits, rows top to bottom, 3 bits per row.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Width in pixels of `n` drawn with `draw_number`.
+fn number_width(n: usize, scale: u32) -> u32 {
+    n.to_string().len() as u32 * 4 * scale - scale
+}
+
+fn draw_number(img: &mut RgbaImage, n: usize, x: u32, y: u32, scale: u32, colour: Rgba<u8>) {
+    for (i, digit) in n.to_string().bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        let gx = x + i as u32 * 4 * scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (px, py) = (gx + col * scale + dx, y + row as u32 * scale + dy);
+                        if px < img.width() && py < img.height() {
+                            img.put_pixel(px, py, colour);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Renders layer `y` seen from above, x to the right and z downwards. Blocks are drawn with their
+/// texture from `load_textures`, else their registry colour, else a colour derived from their id.
+pub fn render_layer(arr: &ArrayModel, y: usize, textures: &[(Block, DynamicImage)], opts: &GuideOptions) -> Option<RgbaImage> {
+    render_layer_with(arr, y, &mut Swatches::new(opts.cell_size, textures, &arr.registry), opts)
+}
+
+fn render_layer_with(arr: &ArrayModel, y: usize, swatches: &mut Swatches, opts: &GuideOptions) -> Option<RgbaImage> {
+    let layer = arr.layer_view(y)?;
+    let below = y.checked_sub(1).and_then(|y| arr.layer_view(y));
+    let (dx, dz) = (arr.dims.0 as u32, arr.dims.2 as u32);
+    let cell = opts.cell_size.max(1);
+    let scale = (cell / 8).max(1);
+    let text_h = 5 * scale;
+    let label_w = number_width(arr.dims.0.max(arr.dims.2).max(arr.dims.1) as usize, scale);
+    let margin = match opts.labels {
+        true => label_w.max(text_h) + 2 * scale,
+        false => 0,
+    };
+
+    let mut img = RgbaImage::from_pixel(margin + dx * cell, margin + dz * cell, Rgba(opts.background));
+    for x in 0..dx {
+        for z in 0..dz {
+            let b = layer.get(x as usize, z as usize).unwrap_or(AIR);
+            if b == AIR {
+                continue;
+            }
+            let (px, py) = (margin + x * cell, margin + z * cell);
+            let swatch = swatches.get(b);
+            for (sx, sy, p) in swatch.enumerate_pixels() {
+                blend(&mut img, px + sx, py + sy, *p);
+            }
+
+            let is_new = below.is_none_or(|l| l.get(x as usize, z as usize) != Some(b));
+            if opts.highlight_new && is_new {
+                let t = (cell / 8).max(1);
+                for sy in 0..cell {
+                    for sx in 0..cell {
+                        if sx < t || sy < t || sx >= cell - t || sy >= cell - t {
+                            img.put_pixel(px + sx, py + sy, Rgba(opts.highlight));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if opts.grid && dx > 0 && dz > 0 {
+        let line = |i: u32| match i % 5 {
+            0 => Rgba([64, 64, 64, 255]),
+            _ => Rgba([160, 160, 160, 255]),
+        };
+        for x in 0..=dx {
+            let px = (margin + x * cell).min(img.width() - 1);
+            for py in margin..img.height() {
+                blend(&mut img, px, py, line(x));
+            }
+        }
+        for z in 0..=dz {
+            let py = (margin + z * cell).min(img.height() - 1);
+            for px in margin..img.width() {
+                blend(&mut img, px, py, line(z));
+            }
+        }
+    }
+
+    if opts.labels {
+        let black = Rgba([0, 0, 0, 255]);
+        // Skip labels which would overlap their neighbours.
+        let step = (label_w + 2 * scale).div_ceil(cell).max(1) as usize;
+        for x in (0..dx as usize).step_by(step) {
+            let w = number_width(x, scale);
+            let px = (margin + x as u32 * cell + cell / 2).saturating_sub(w / 2);
+            draw_number(&mut img, x, px, scale, scale, black);
+        }
+        for z in (0..dz as usize).step_by((text_h + scale).div_ceil(cell).max(1) as usize) {
+            let w = number_width(z, scale);
+            let py = (margin + z as u32 * cell + cell / 2).saturating_sub(text_h / 2);
+            draw_number(&mut img, z, margin - scale - w, py, scale, black);
+        }
+        draw_number(&mut img, y, scale, scale, scale, Rgba(opts.highlight));
+    }
+    Some(img)
+}
+
+/// Renders every layer from the bottom up, skipping empty layers.
+pub fn build_guide(arr: &ArrayModel, textures: &[(Block, DynamicImage)], opts: &GuideOptions) -> Vec<(usize, RgbaImage)> {
+    let mut swatches = Swatches::new(opts.cell_size, textures, &arr.registry);
+    arr.layers()
+        .filter(|layer| !layer.is_empty())
+        .filter_map(|layer| Some((layer.y, render_layer_with(arr, layer.y, &mut swatches, opts)?)))
+        .collect()
+}
+
+/// Writes `layer_<y>.png` for every non empty layer into `dir`, returns the written paths.
+pub fn save_guide<P>(arr: &ArrayModel, textures: &[(Block, DynamicImage)], dir: P, opts: &GuideOptions) -> Result<Vec<PathBuf>>
+where
+        P: AsRef<Path> + std::fmt::Debug, {
+    std::fs::create_dir_all(&dir)?;
+    let width = arr.dims.1.to_string().len();
+    let mut paths = Vec::new();
+    for (y, img) in build_guide(arr, textures, opts) {
+        let path = dir.as_ref().join(format!("layer_{:0width$}.png", y, width = width));
+        img.save(&path)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Faces, Model, Points};
+    use crate::model2arr::model_2_arr_with_transform;
+    use crate::registry::DEFAULT_BLOCK;
+    use crate::grid::GridTransform;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn renders_default_block() {
+        let mut arr = ArrayModel::new((2, 2, 2), 1.0);
+        arr.set((0, 0, 0), DEFAULT_BLOCK);
+        let img = render_layer(&arr, 0, &[], &GuideOptions { labels: false, grid: false, ..Default::default() }).unwrap();
+        assert_eq!(img.dimensions(), (32, 32));
+    }
+
+    #[test]
+    fn renders_voxelized_model() {
+        let model = Model::new(
+            Points(vec![Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 3.0)]),
+            Faces(vec![[0, 1, 2]]),
+        );
+        let (arr, _) = model_2_arr_with_transform(&model, (4, 4, 4), GridTransform::default(), 1.0).unwrap();
+        assert!(arr.iter_filled().any(|(_, b)| b == DEFAULT_BLOCK));
+        assert_eq!(build_guide(&arr, &[], &GuideOptions::default()).len(), 1);
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+use crate::grid::{offset_coord, Neighbourhood};
+use crate::model2arr::{ArrayModel, Block};
+
+#[derive(Debug, Clone)]
+pub struct HollowOptions {
+    /// Voxels within this many blocks of the exterior are kept.
+    pub thickness: usize,
+    /// How distance to the exterior is measured, `Six` gives walls that are watertight along faces.
+    pub neighbourhood: Neighbourhood,
+    /// Keep vertical pillars inside the hollow on every column where `x` and `z` are multiples of
+    /// this spacing.
+    pub pillar_spacing: Option<usize>,
+    /// Block used for pillars, otherwise the original block is kept.
+    pub pillar_block: Option<Block>,
+}
+
+impl Default for HollowOptions {
+    fn default() -> Self {
+        Self {
+            thickness: 1,
+            neighbourhood: Neighbourhood::Six,
+            pillar_spacing: None,
+            pillar_block: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct HollowReport {
+    pub removed: usize,
+    /// Interior voxels kept as pillars.
+    pub pillars: usize,
+}
+
+/// Removes every filled voxel further than `thickness` blocks from the exterior.
+///
+/// The exterior is the air reachable from the edge of the grid, so enclosed air pockets do not
+/// count and the shell around them is hollowed too.
+pub fn hollow(arr: &ArrayModel, opts: &HollowOptions) -> (ArrayModel, HollowReport) {
+    let dims = arr.dims;
+    let faces = Neighbourhood::Six.offsets();
+    let offsets = opts.neighbourhood.offsets();
+
+    // Flood fill the exterior air from the grid boundary.
+    let mut exterior = vec![false; arr.blocks.len()];
+    let mut queue = VecDeque::new();
+    for (i, &b) in arr.blocks.iter().enumerate() {
+        let c_xyz = arr.coord(i);
+        let on_boundary = faces.iter().any(|&d| offset_coord(c_xyz, d, dims).is_none());
+        if on_boundary && b == 0 {
+            exterior[i] = true;
+            queue.push_back(c_xyz);
+        }
+    }
+    while let Some(c_xyz) = queue.pop_front() {
+        for &d in faces.iter() {
+            if let Some(n) = offset_coord(c_xyz, d, dims) {
+                let j = arr.index(n).unwrap();
+                if !exterior[j] && arr.blocks[j] == 0 {
+                    exterior[j] = true;
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+
+    // Distance from the exterior through filled voxels, 1 for voxels touching the exterior.
+    let mut distance = vec![usize::MAX; arr.blocks.len()];
+    for (i, &b) in arr.blocks.iter().enumerate() {
+        if b == 0 {
+            continue;
+        }
+        let c_xyz = arr.coord(i);
+        let touches_exterior = offsets.iter().any(|&d| match offset_coord(c_xyz, d, dims) {
+            Some(n) => exterior[arr.index(n).unwrap()],
+            None => true,
+        });
+        if touches_exterior {
+            distance[i] = 1;
+            queue.push_back(c_xyz);
+        }
+    }
+    while let Some(c_xyz) = queue.pop_front() {
+        let next = distance[arr.index(c_xyz).unwrap()] + 1;
+        if next > opts.thickness {
+            continue;
+        }
+        for &d in offsets.iter() {
+            if let Some(n) = offset_coord(c_xyz, d, dims) {
+                let j = arr.index(n).unwrap();
+                if arr.blocks[j] != 0 && distance[j] > next {
+                    distance[j] = next;
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+
+    let mut hollowed = arr.clone();
+    let mut report = HollowReport::default();
+    for (i, &b) in arr.blocks.iter().enumerate() {
+        if b == 0 || distance[i] <= opts.thickness {
+            continue;
+        }
+        let (x, _y, z) = arr.coord(i);
+        let pillar = opts.pillar_spacing.is_some_and(|s| s > 0 && x % s == 0 && z % s == 0);
+        if pillar {
+            report.pillars += 1;
+            if let Some(b) = opts.pillar_block {
+                hollowed.blocks[i] = b;
+            }
+        } else {
+            report.removed += 1;
+            hollowed.blocks[i] = 0;
+        }
+    }
+    (hollowed, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(n: u16) -> ArrayModel {
+        let mut arr = ArrayModel::new((n, n, n), 1.0);
+        arr.blocks.fill(1);
+        arr
+    }
+
+    #[test]
+    fn hollows_to_shell() {
+        let (hollowed, report) = hollow(&solid(5), &HollowOptions::default());
+        assert_eq!(report.removed, 27);
+        assert_eq!(hollowed.get((2, 2, 2)), Some(0));
+        assert_eq!(hollowed.get((0, 2, 2)), Some(1));
+
+        let thick = HollowOptions { thickness: 2, ..Default::default() };
+        assert_eq!(hollow(&solid(5), &thick).1.removed, 1);
+    }
+
+    #[test]
+    fn keeps_pillars() {
+        let opts = HollowOptions { pillar_spacing: Some(2), pillar_block: Some(9), ..Default::default() };
+        let (hollowed, report) = hollow(&solid(5), &opts);
+        assert_eq!(report.pillars, 3);
+        assert_eq!(hollowed.get((2, 1, 2)), Some(9));
+        assert_eq!(hollowed.get((1, 1, 1)), Some(0));
+    }
+}
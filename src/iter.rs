@@ -0,0 +1,144 @@
+use crate::model2arr::{ArrayModel, Block};
+
+/// View of a single Y layer of an `ArrayModel`.
+#[derive(Debug, Clone, Copy)]
+pub struct Layer<'a> {
+    pub y: usize,
+    /// Stored as `blocks[x * dims.z + z]`
+    pub blocks: &'a [Block],
+    dim_z: usize,
+}
+
+impl<'a> Layer<'a> {
+    pub fn get(&self, x: usize, z: usize) -> Option<Block> {
+        if z >= self.dim_z {
+            return None;
+        }
+        self.blocks.get(x * self.dim_z + z).copied()
+    }
+
+    /// Z rows of the layer, in x order.
+    pub fn rows(&self) -> impl Iterator<Item = &'a [Block]> {
+        self.blocks.chunks(self.dim_z.max(1))
+    }
+
+    /// Non air blocks of the layer as (x, z, block).
+    pub fn iter_filled(&self) -> impl Iterator<Item = (usize, usize, Block)> + 'a {
+        let dim_z = self.dim_z;
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b != 0)
+            .map(move |(i, &b)| (i / dim_z, i % dim_z, b))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|&b| b == 0)
+    }
+}
+
+/// View of the blocks along Y at a single (x, z).
+#[derive(Debug, Clone, Copy)]
+pub struct Column<'a> {
+    pub x: usize,
+    pub z: usize,
+    arr: &'a ArrayModel,
+}
+
+impl<'a> Column<'a> {
+    pub fn get(&self, y: usize) -> Option<Block> {
+        self.arr.get((self.x, y, self.z))
+    }
+
+    /// Blocks from the bottom to the top of the column.
+    pub fn iter(&self) -> impl Iterator<Item = Block> + 'a {
+        let (arr, x, z) = (self.arr, self.x, self.z);
+        (0..arr.dims.1 as usize).map(move |y| arr.get((x, y, z)).unwrap_or(0))
+    }
+
+    /// Non air blocks of the column as (y, block).
+    pub fn iter_filled(&self) -> impl Iterator<Item = (usize, Block)> + 'a {
+        self.iter().enumerate().filter(|&(_, b)| b != 0)
+    }
+
+    /// Highest non air block as (y, block).
+    pub fn top(&self) -> Option<(usize, Block)> {
+        (0..self.arr.dims.1 as usize)
+            .rev()
+            .map(|y| (y, self.get(y).unwrap_or(0)))
+            .find(|&(_, b)| b != 0)
+    }
+}
+
+impl ArrayModel {
+    /// All non air voxels as ((x, y, z), block), in y, x, z order.
+    pub fn iter_filled(&self) -> impl Iterator<Item = ((usize, usize, usize), Block)> + '_ {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b != 0)
+            .map(move |(i, &b)| (self.coord(i), b))
+    }
+
+    /// Coordinates of every voxel set to `block`.
+    pub fn iter_block(&self, block: Block) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.iter_filled()
+            .filter(move |&(_, b)| b == block)
+            .map(|(c_xyz, _)| c_xyz)
+    }
+
+    /// Layers from the bottom (y = 0) to the top.
+    pub fn layers(&self) -> impl Iterator<Item = Layer<'_>> {
+        let len = self.dims.0 as usize * self.dims.2 as usize;
+        let dim_z = self.dims.2 as usize;
+        (0..self.dims.1 as usize).map(move |y| Layer {
+            y,
+            blocks: &self.blocks[y * len..(y + 1) * len],
+            dim_z,
+        })
+    }
+
+    pub fn layer_view(&self, y: usize) -> Option<Layer<'_>> {
+        Some(Layer { y, blocks: self.layer(y)?, dim_z: self.dims.2 as usize })
+    }
+
+    /// Columns in x, z order.
+    pub fn columns(&self) -> impl Iterator<Item = Column<'_>> {
+        let dim_z = self.dims.2 as usize;
+        (0..self.dims.0 as usize * dim_z).map(move |i| Column { x: i / dim_z, z: i % dim_z, arr: self })
+    }
+
+    pub fn column(&self, x: usize, z: usize) -> Option<Column<'_>> {
+        if x >= self.dims.0 as usize || z >= self.dims.2 as usize {
+            return None;
+        }
+        Some(Column { x, z, arr: self })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model2arr::ArrayModel;
+
+    #[test]
+    fn layers_and_columns() {
+        let mut arr = ArrayModel::new((2, 3, 4), 1.0);
+        arr.set((1, 0, 3), 5);
+        arr.set((1, 2, 3), 6);
+        assert_eq!(arr.iter_filled().collect::<Vec<_>>(), [((1, 0, 3), 5), ((1, 2, 3), 6)]);
+        assert_eq!(arr.iter_block(6).collect::<Vec<_>>(), [(1, 2, 3)]);
+
+        let layers = arr.layers().collect::<Vec<_>>();
+        assert_eq!(layers.len(), 3);
+        assert!(layers[1].is_empty());
+        assert_eq!(layers[0].get(1, 3), Some(5));
+        assert_eq!(layers[0].iter_filled().collect::<Vec<_>>(), [(1, 3, 5)]);
+        assert_eq!(layers[2].rows().count(), 2);
+
+        assert_eq!(arr.columns().count(), 8);
+        let column = arr.column(1, 3).unwrap();
+        assert_eq!(column.iter().collect::<Vec<_>>(), [5, 0, 6]);
+        assert_eq!(column.top(), Some((2, 6)));
+        assert!(arr.column(2, 0).is_none());
+    }
+}
@@ -1,56 +1,69 @@
+//! ```no_run
+//! use modelutils_rs::{fit, model, model2arr};
+//! use modelutils_rs::float;
+//! use modelutils_rs::model2arr::uint;
+//!
+//! const PATH: &str = "shapes/rotated_puppet.obj";
+//!
+//! const RESOLUTION: float = 100.0;
+//! const S: uint = 100;
+//! const DIMS: (uint, uint, uint) = (S, S, S);
+//!
+//! fn main() {
+//!     let (models, _materials) = modelutils_rs::load_default(PATH).unwrap();
+//!
+//!     let models = models
+//!         .into_iter()
+//!         .map(|m| model::Model::new(
+//!             model::Points::from_flat_vec(m.mesh.positions),
+//!             model::Faces::from_triangles(m.mesh.indices),
+//!         ))
+//!         .collect::<Vec<model::Model>>();
+//!
+//!     if let Some(mut model) = models.into_iter().next() {
+//!         // Align model to origin and scale it to fit in the grid
+//!         fit::fit_to_grid(&mut model, DIMS, &fit::FitOptions::default()).unwrap();
+//!
+//!         // Convert to array
+//!         let arr3d = model2arr::model_2_arr(model, DIMS, RESOLUTION);
+//!
+//!         // Save to json for unity
+//!         model2arr::arr_2_json("arr.json", arr3d).unwrap();
+//!     }
+//! }
+//! ```
+
 use serde::{Deserialize, Serialize};
 use crate::model2arr::Block;
 
-/// ```
-/// use modelutils_rs::{model, model2arr};
-/// use modelutils_rs::vec3::Vec3;
-/// use modelutils_rs::float;
-///
-/// const PATH: &str = "shapes/rotated_puppet.obj";
-///
-/// const RESOLUTION: float = 100.0;
-/// const S: usize = 100;
-/// const DIMS: (usize, usize, usize) = (S, S, S);
-/// const SCALE_DIMS: (float, float, float) = ((DIMS.0 - 1) as float, (DIMS.1 - 1) as float, (DIMS.2 - 1) as float);
-///
-/// fn main() {
-///     let scale_vec = Vec3::new(SCALE_DIMS.0, SCALE_DIMS.1, SCALE_DIMS.2);
-///     let (models, _materials) = modelutils_rs::load_default(&PATH).unwrap();
-///
-///     let mut models = models
-///         .into_iter()
-///         .map(|m| model::Model::new(
-///             model::Points::from_flat_vec(m.mesh.positions),
-///             model::Faces::from_triangles(m.mesh.indices),
-///         ))
-///         .collect::<Vec<model::Model>>();
-///
-///     for mut model in models.into_iter() {
-///         // Align model to origin
-///         let bounds = model.model_dims();
-///         model.mv(bounds.0 * Vec3::from_scalar(-1.0));
-///
-///         // Scale model to fit in 10x10x10 cube
-///         let scale = model.scale_for_box(scale_vec);
-///         model.scale(Vec3::from_scalar(scale.min_val()));
-///
-///         // Convert to array
-///         let arr3d = model2arr::model_2_arr(model, DIMS, RESOLUTION);
-///
-///         // Save to json for unity
-///         model2arr::arr_2_json("arr.json", arr3d).unwrap();
-///
-///         break;
-///     }
-/// }
-/// ```
-
+pub mod error;
 pub mod vec3;
 pub mod coords;
 pub mod utils;
 pub mod model;
 pub mod model2arr;
 pub mod vec2;
+pub mod grid;
+pub mod fit;
+pub mod chunked;
+pub mod octree;
+pub mod iter;
+pub mod registry;
+pub mod atlas;
+pub mod morphology;
+pub mod hollow;
+pub mod components;
+pub mod reorient;
+pub mod region;
+pub mod csg;
+pub mod lod;
+pub mod diff;
+pub mod materials;
+pub mod guide;
+pub mod render;
+mod raster;
+
+pub use error::{Error, Result};
 
 #[allow(non_camel_case_types)]
 pub type float = f32;
@@ -72,23 +85,125 @@ pub fn load_default<P>(p: P) -> tobj::LoadResult
 }
 
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Optional file in a textures directory listing the texture order, in the `TextureNames` format.
+pub const TEXTURE_MANIFEST: &str = "textures.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TextureNames {
     textures: Vec<String>,
 }
 
+impl TextureNames {
+    pub fn new(textures: Vec<String>) -> Self {
+        Self { textures }
+    }
+
+    /// Texture file names, texture `i` has block id `i + 1`.
+    pub fn textures(&self) -> &[String] {
+        &self.textures
+    }
+
+    /// Block id of a texture file name.
+    pub fn id(&self, fname: &str) -> Option<Block> {
+        self.textures.iter().position(|t| t == fname).map(|i| i as Block + 1)
+    }
+
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+            P: AsRef<std::path::Path> + std::fmt::Debug, {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+            P: AsRef<std::path::Path> + std::fmt::Debug, {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Texture `i` gets block id `i + 1`, as 0 is air. Earlier versions numbered textures from 0, so
+/// arrays saved with those ids are one lower than the ids returned here.
+///
+/// Textures are ordered by the directory's `TEXTURE_MANIFEST` if it exists, remaining images are
+/// appended sorted by file name. Files which are not images (e.g. Unity `.meta` files) are skipped.
 pub fn load_textures<P>(dir: P) -> (TextureNames, Vec<(Block, image::DynamicImage)>)
 where
         P: AsRef<std::path::Path> + std::fmt::Debug, {
-    let mut textures = Vec::new();
-    let mut texture_names = Vec::new();
-    for (i, entry) in std::fs::read_dir(dir).unwrap().enumerate() {
-        let entry = entry.unwrap();
+    try_load_textures(dir).expect("Failed to load textures")
+}
+
+pub fn try_load_textures<P>(dir: P) -> Result<(TextureNames, Vec<(Block, image::DynamicImage)>)>
+where
+        P: AsRef<std::path::Path> + std::fmt::Debug, {
+    let manifest = dir.as_ref().join(TEXTURE_MANIFEST);
+    let pinned = if manifest.is_file() {
+        TextureNames::load(&manifest)?
+    } else {
+        TextureNames::new(vec![])
+    };
+    try_load_textures_pinned(dir, &pinned)
+}
+
+/// Like `try_load_textures`, but the textures of a previously saved `TextureNames` keep their ids.
+/// New images in the directory are appended sorted by file name, a pinned texture which no longer
+/// exists is an error.
+pub fn try_load_textures_pinned<P>(dir: P, pinned: &TextureNames) -> Result<(TextureNames, Vec<(Block, image::DynamicImage)>)>
+where
+        P: AsRef<std::path::Path> + std::fmt::Debug, {
+    let mut texture_names = pinned.textures.clone();
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
         let path = entry.path();
-        let img = image::open(path).unwrap();
-        textures.push((i as Block, img));
-        let fname = entry.file_name().into_string().unwrap();
-        texture_names.push(fname);
+        if !path.is_file() || image::ImageFormat::from_path(&path).is_err() {
+            continue;
+        }
+        let fname = entry.file_name().into_string().map_err(Error::InvalidFileName)?;
+        if !texture_names.contains(&fname) {
+            found.push(fname);
+        }
+    }
+    found.sort();
+    texture_names.extend(found);
+
+    let mut textures = Vec::with_capacity(texture_names.len());
+    for (i, fname) in texture_names.iter().enumerate() {
+        let path = dir.as_ref().join(fname);
+        if !path.is_file() {
+            return Err(Error::MissingTexture(fname.clone()));
+        }
+        let img = image::open(path)?;
+        textures.push((i as Block + 1, img));
     }
-    (TextureNames { textures: texture_names }, textures)
-}
\ No newline at end of file
+    Ok((TextureNames { textures: texture_names }, textures))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_texture_ids() {
+        let dir = std::env::temp_dir().join(format!("modelutils_textures_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.png", "a.png", "c.png"] {
+            image::RgbaImage::new(1, 1).save(dir.join(name)).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "not a texture").unwrap();
+
+        let (names, textures) = try_load_textures(&dir).unwrap();
+        assert_eq!(names.textures(), ["a.png", "b.png", "c.png"]);
+        assert_eq!(textures.iter().map(|(b, _)| *b).collect::<Vec<_>>(), [1, 2, 3]);
+
+        let pinned = TextureNames::new(vec!["c.png".to_string()]);
+        let (names, _) = try_load_textures_pinned(&dir, &pinned).unwrap();
+        assert_eq!(names.id("c.png"), Some(1));
+        assert_eq!(names.id("a.png"), Some(2));
+
+        let missing = TextureNames::new(vec!["gone.png".to_string()]);
+        assert!(matches!(try_load_textures_pinned(&dir, &missing), Err(Error::MissingTexture(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
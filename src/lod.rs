@@ -0,0 +1,160 @@
+use crate::model2arr::{uint, ArrayModel, Block};
+use super::float;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockChoice {
+    /// Most common block in the cell, ties go to the lowest id.
+    Majority,
+    /// Registry block whose colour is closest to the average colour of the cell. Falls back to
+    /// `Majority` for cells whose blocks have no colour.
+    ColourAverage,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownsampleOptions {
+    pub choice: BlockChoice,
+    /// A cell is filled if more than this fraction of its voxels are filled. The default of 0 keeps
+    /// thin surfaces, 0.5 is a majority vote between air and blocks.
+    pub fill_threshold: float,
+}
+
+impl Default for DownsampleOptions {
+    fn default() -> Self {
+        Self { choice: BlockChoice::Majority, fill_threshold: 0.0 }
+    }
+}
+
+/// Shrinks the grid by an integer `factor` per axis, every factor^3 cell becomes one voxel.
+///
+/// The voxel size is scaled by `factor`, so the result stays in place in world space.
+pub fn downsample(arr: &ArrayModel, factor: usize, opts: &DownsampleOptions) -> ArrayModel {
+    let factor = factor.max(1);
+    let shrink = |d: uint| (d as usize).div_ceil(factor) as uint;
+    let dims = (shrink(arr.dims.0), shrink(arr.dims.1), shrink(arr.dims.2));
+
+    let mut transform = arr.transform.clone();
+    transform.voxel_size = transform.voxel_size * factor as float;
+    let mut out = ArrayModel::with_transform(dims, transform, arr.resolution);
+    out.registry = arr.registry.clone();
+
+    let palette = arr.registry
+        .iter()
+        .filter_map(|(id, def)| def.colour.map(|c| (id, c)))
+        .collect::<Vec<(Block, [u8; 4])>>();
+
+    let mut counts: Vec<(Block, usize)> = Vec::new();
+    for i in 0..out.blocks.len() {
+        let (cx, cy, cz) = out.coord(i);
+        counts.clear();
+        let mut volume = 0;
+        for x in cx * factor..((cx + 1) * factor).min(arr.dims.0 as usize) {
+            for y in cy * factor..((cy + 1) * factor).min(arr.dims.1 as usize) {
+                for z in cz * factor..((cz + 1) * factor).min(arr.dims.2 as usize) {
+                    volume += 1;
+                    let b = arr.get((x, y, z)).unwrap_or(0);
+                    if b == 0 {
+                        continue;
+                    }
+                    match counts.iter_mut().find(|(block, _)| *block == b) {
+                        Some((_, n)) => *n += 1,
+                        None => counts.push((b, 1)),
+                    }
+                }
+            }
+        }
+
+        let filled = counts.iter().map(|(_, n)| n).sum::<usize>();
+        if filled == 0 || (filled as float) <= opts.fill_threshold * volume as float {
+            continue;
+        }
+        let colour = match opts.choice {
+            BlockChoice::ColourAverage => nearest_colour(arr, &counts, &palette),
+            BlockChoice::Majority => None,
+        };
+        out.blocks[i] = colour.unwrap_or_else(|| {
+            counts.sort_by_key(|&(b, n)| (std::cmp::Reverse(n), b));
+            counts[0].0
+        });
+    }
+    out
+}
+
+/// Registry block closest to the weighted average colour of `counts`.
+fn nearest_colour(arr: &ArrayModel, counts: &[(Block, usize)], palette: &[(Block, [u8; 4])]) -> Option<Block> {
+    let mut sum = [0.0 as float; 3];
+    let mut total = 0;
+    for &(b, n) in counts.iter() {
+        if let Some(c) = arr.registry.get(b).and_then(|def| def.colour) {
+            for (s, &c) in sum.iter_mut().zip(c.iter()) {
+                *s += c as float * n as float;
+            }
+            total += n;
+        }
+    }
+    if total == 0 {
+        return None;
+    }
+    let avg = sum.map(|s| s / total as float);
+    palette
+        .iter()
+        .min_by(|(_, a), (_, b)| {
+            let dist = |c: &[u8; 4]| (0..3).map(|i| (c[i] as float - avg[i]).powi(2)).sum::<float>();
+            dist(a).total_cmp(&dist(b))
+        })
+        .map(|&(id, _)| id)
+}
+
+/// Level 0 is a copy of the grid, every following level is downsampled by 2 from the previous.
+/// Stops early once a level is a single voxel.
+pub fn lod_pyramid(arr: &ArrayModel, levels: usize, opts: &DownsampleOptions) -> Vec<ArrayModel> {
+    let mut pyramid = vec![arr.clone()];
+    while pyramid.len() < levels {
+        let last = pyramid.last().unwrap();
+        if last.dims.0 <= 1 && last.dims.1 <= 1 && last.dims.2 <= 1 {
+            break;
+        }
+        pyramid.push(downsample(last, 2, opts));
+    }
+    pyramid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::BlockDef;
+
+    #[test]
+    fn majority_and_threshold() {
+        let mut arr = ArrayModel::new((4, 2, 2), 1.0);
+        arr.set((0, 0, 0), 1);
+        arr.set((0, 1, 0), 2);
+        arr.set((1, 1, 0), 2);
+        arr.set((2, 0, 0), 3);
+        let down = downsample(&arr, 2, &DownsampleOptions::default());
+        assert_eq!(down.dims, (2, 1, 1));
+        assert_eq!(down.blocks, [2, 3]);
+        assert_eq!(down.transform.voxel_size.x, 2.0);
+
+        let opts = DownsampleOptions { fill_threshold: 0.25, ..Default::default() };
+        assert_eq!(downsample(&arr, 2, &opts).blocks, [2, 0]);
+    }
+
+    #[test]
+    fn colour_average() {
+        let mut arr = ArrayModel::new((2, 1, 1), 1.0);
+        arr.registry.insert(1, BlockDef { colour: Some([255, 0, 0, 255]), ..BlockDef::new("red") });
+        arr.registry.insert(2, BlockDef { colour: Some([0, 0, 255, 255]), ..BlockDef::new("blue") });
+        arr.registry.insert(3, BlockDef { colour: Some([128, 0, 128, 255]), ..BlockDef::new("purple") });
+        arr.blocks.copy_from_slice(&[1, 2]);
+        let opts = DownsampleOptions { choice: BlockChoice::ColourAverage, ..Default::default() };
+        assert_eq!(downsample(&arr, 2, &opts).blocks, [3]);
+    }
+
+    #[test]
+    fn pyramid() {
+        let mut arr = ArrayModel::new((8, 8, 8), 1.0);
+        arr.blocks.fill(1);
+        let levels = lod_pyramid(&arr, 3, &DownsampleOptions::default());
+        assert_eq!(levels.iter().map(|l| l.dims).collect::<Vec<_>>(), [(8, 8, 8), (4, 4, 4), (2, 2, 2)]);
+    }
+}
@@ -1,5 +1,4 @@
-use modelutils_rs::{model, model2arr};
-use modelutils_rs::vec3::Vec3;
+use modelutils_rs::{fit, model, model2arr};
 use modelutils_rs::float;
 use modelutils_rs::model2arr::uint;
 
@@ -8,12 +7,10 @@ const PATH: &str = "shapes/cottage/cottage_obj.obj";
 const RESOLUTION: float = 100.0;
 const S: uint = 50;
 const DIMS: (uint, uint, uint) = (S, S, S);
-const SCALE_DIMS: (float, float, float) = ((DIMS.0 - 1) as float, (DIMS.1 - 1) as float, (DIMS.2 - 1) as float);
 
 
 fn main() {
-    let scale_vec = Vec3::new(SCALE_DIMS.0, SCALE_DIMS.1, SCALE_DIMS.2);
-    let (models, _materials) = modelutils_rs::load_default(&PATH).unwrap();
+    let (models, _materials) = modelutils_rs::load_default(PATH).unwrap();
 
     let models = models
         .into_iter()
@@ -23,26 +20,15 @@ fn main() {
         ))
         .collect::<Vec<model::Model>>();
 
-    for mut model in models.into_iter().skip(0) {
-
-        // Align model to origin
-        let bounds = model.model_dims();
-        model.mv(bounds.0 * Vec3::from_scalar(-1.0));
-
-        // Scale model to fit in 10x10x10 cube
-        let scale = model.scale_for_box(scale_vec.clone());
-        model.scale(Vec3::from_scalar(scale.min_val()));
-
-        // Convert to array
-        let arr3d = model2arr::model_2_arr(
-            model,
-            DIMS,
-            RESOLUTION,
-        );
-
-        // Save to json for unity
-        model2arr::arr_2_json("arr.json", arr3d).unwrap();
-
-        // break;
-    }
-}
\ No newline at end of file
+    // Fit all parts of the model into one grid and convert to array
+    let (arr3d, _report) = model2arr::scene_2_arr(
+        &models,
+        DIMS,
+        &fit::FitOptions::default(),
+        RESOLUTION,
+        false,
+    ).unwrap();
+
+    // Save to json for unity
+    model2arr::arr_2_json("arr.json", arr3d).unwrap();
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use serde::{Deserialize, Serialize};
+use crate::error::Result;
+use crate::model2arr::{ArrayModel, Block};
+
+pub const STACK_SIZE: usize = 64;
+/// Stacks which fit in one shulker box.
+pub const SHULKER_SLOTS: usize = 27;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Material {
+    pub block: Block,
+    pub name: String,
+    pub count: usize,
+    /// Full stacks.
+    pub stacks: usize,
+    /// Blocks left over after the full stacks.
+    pub remainder: usize,
+    /// Shulker boxes needed to carry every stack, including the partial one.
+    pub shulker_boxes: usize,
+}
+
+impl Material {
+    fn new(block: Block, name: &str, count: usize) -> Self {
+        Self {
+            block,
+            name: name.to_string(),
+            count,
+            stacks: count / STACK_SIZE,
+            remainder: count % STACK_SIZE,
+            shulker_boxes: count.div_ceil(STACK_SIZE).div_ceil(SHULKER_SLOTS),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct BillOfMaterials {
+    /// Most used blocks first.
+    pub materials: Vec<Material>,
+    pub total: usize,
+}
+
+/// Counts every non air block of the grid, named by the grid's registry. Grids voxelized with a
+/// registry from `BlockRegistry::from_textures` are named after their textures.
+pub fn bill_of_materials(arr: &ArrayModel) -> BillOfMaterials {
+    let mut counts = BTreeMap::<Block, usize>::new();
+    for (_, b) in arr.iter_filled() {
+        *counts.entry(b).or_default() += 1;
+    }
+    let mut materials = counts
+        .into_iter()
+        .map(|(b, count)| Material::new(b, arr.registry.name(b), count))
+        .collect::<Vec<Material>>();
+    materials.sort_by_key(|m| (std::cmp::Reverse(m.count), m.block));
+    let total = materials.iter().map(|m| m.count).sum();
+    BillOfMaterials { materials, total }
+}
+
+impl BillOfMaterials {
+    /// Aligned table for printing.
+    pub fn to_text(&self) -> String {
+        let width = self.materials.iter().map(|m| m.name.len()).max().unwrap_or(0).max("block".len());
+        let mut out = String::new();
+        writeln!(out, "{:<width$}  {:>8}  {:>14}  {:>8}", "block", "count", "stacks", "shulkers").unwrap();
+        for m in self.materials.iter() {
+            let stacks = format!("{} x 64 + {}", m.stacks, m.remainder);
+            writeln!(out, "{:<width$}  {:>8}  {:>14}  {:>8}", m.name, m.count, stacks, m.shulker_boxes).unwrap();
+        }
+        writeln!(out, "{:<width$}  {:>8}", "total", self.total).unwrap();
+        out
+    }
+
+    /// One row per block: `block,name,count,stacks,remainder,shulker_boxes`.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("block,name,count,stacks,remainder,shulker_boxes\n");
+        for m in self.materials.iter() {
+            let name = match m.name.contains([',', '"', '\n']) {
+                true => format!("\"{}\"", m.name.replace('"', "\"\"")),
+                false => m.name.clone(),
+            };
+            writeln!(out, "{},{},{},{},{},{}", m.block, name, m.count, m.stacks, m.remainder, m.shulker_boxes).unwrap();
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::BlockDef;
+
+    #[test]
+    fn counts_stacks_and_shulkers() {
+        let mut arr = ArrayModel::new((40, 40, 1), 1.0);
+        arr.blocks.fill(1);
+        arr.set((0, 0, 0), 2);
+        arr.registry.insert(1, BlockDef::new("stone, smooth"));
+        let bom = bill_of_materials(&arr);
+        assert_eq!(bom.total, 1600);
+        assert_eq!(bom.materials[0], Material {
+            block: 1,
+            name: "stone, smooth".to_string(),
+            count: 1599,
+            stacks: 24,
+            remainder: 63,
+            shulker_boxes: 1,
+        });
+        assert_eq!(bom.materials[1].name, "unknown");
+        assert!(bom.to_csv().contains("1,\"stone, smooth\",1599,24,63,1\n"));
+        assert!(bom.to_text().lines().last().unwrap().contains("1600"));
+        let json: BillOfMaterials = serde_json::from_str(&bom.to_json().unwrap()).unwrap();
+        assert_eq!(json, bom);
+    }
+
+    #[test]
+    fn shulker_boxes_round_up() {
+        assert_eq!(Material::new(1, "a", 27 * 64).shulker_boxes, 1);
+        assert_eq!(Material::new(1, "a", 27 * 64 + 1).shulker_boxes, 2);
+    }
+}
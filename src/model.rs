@@ -3,6 +3,7 @@ use crate::utils;
 use crate::vec2::Vec2;
 use crate::vec3::Vec3;
 use super::float;
+use crate::error::{Error, Result};
 
 pub struct Points(pub Vec<Vec3>);
 
@@ -36,7 +37,7 @@ pub struct TextureCoords(pub Vec<Vec2>);
 
 impl TextureCoords {
     pub fn from_flat_vec(v: Vec<float>) -> Option<Self> {
-        if v.len() % 2 != 0 || v.is_empty() {
+        if !v.len().is_multiple_of(2) || v.is_empty() {
             return None;
         }
 
@@ -52,7 +53,7 @@ pub struct TextureFaces(pub Vec<[usize; 3]>);
 
 impl TextureFaces {
     pub fn from_triangles(indices: Vec<u32>) -> Option<Self> {
-        if indices.len() % 3 != 0 || indices.is_empty() {
+        if !indices.len().is_multiple_of(3) || indices.is_empty() {
             return None;
         }
         let mut faces = Vec::with_capacity(indices.len() / 3);
@@ -80,7 +81,11 @@ impl Model {
     }
 
     pub fn model_dims(&self) -> (Vec3, Vec3) {
-        let p = &self.vertices.0[0]; // Hopefully doesn't crash :)
+        self.try_model_dims().expect("Model has no vertices")
+    }
+
+    pub fn try_model_dims(&self) -> Result<(Vec3, Vec3)> {
+        let p = self.vertices.0.first().ok_or(Error::EmptyModel)?;
         let mut x = utils::MinMax::new(p.x, p.x);
         let mut y = utils::MinMax::new(p.y, p.y);
         let mut z = utils::MinMax::new(p.z, p.z);
@@ -91,10 +96,10 @@ impl Model {
             z.update(vtx.z);
         }
 
-        (
+        Ok((
             Vec3::new(x.min, y.min, z.min),
             Vec3::new(x.max, y.max, z.max),
-        )
+        ))
     }
 
     pub fn mv(&mut self, move_vec: Vec3) {
@@ -135,4 +140,17 @@ impl Model {
             *vtx *= &scale;
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dims_of_empty_model() {
+        let model = Model::new(Points(vec![]), Faces(vec![]));
+        assert!(matches!(model.try_model_dims(), Err(Error::EmptyModel)));
+        let model = Model::new(Points(vec![Vec3::new(1.0, -2.0, 0.5), Vec3::new(-1.0, 3.0, 0.0)]), Faces(vec![]));
+        let (min, max) = model.try_model_dims().unwrap();
+        assert_eq!([min.x, min.y, min.z, max.x, max.y, max.z], [-1.0, -2.0, 0.0, 1.0, 3.0, 0.5]);
+    }
+}
@@ -7,6 +7,10 @@ use crate::model::Model;
 use crate::vec2::Vec2;
 use super::float;
 use crate::vec3::Vec3;
+use crate::error::{Error, Result};
+use crate::grid::{GridTransform, VoxelGrid};
+use crate::fit::{self, FitOptions};
+use crate::registry::{BlockDef, BlockRegistry, DEFAULT_BLOCK};
 
 #[allow(non_camel_case_types)]
 pub type int = i16;
@@ -26,13 +30,19 @@ struct XAxis {
 #[derive(Serialize, Deserialize)]
 struct JsonModel {
     b: Vec<XAxis>,
+    /// Grid origin
+    o: [float; 3],
+    /// Voxel size
+    s: [float; 3],
+    /// Block definitions
+    p: BlockRegistry,
 }
 
-impl Into<JsonModel> for ArrayModel {
-    fn into(self) -> JsonModel {
-        let mut y_arr = Vec::with_capacity(self.blocks.len());
+impl From<ArrayModel> for JsonModel {
+    fn from(arr: ArrayModel) -> Self {
+        let mut y_arr = Vec::with_capacity(arr.dims.1 as usize);
 
-        for y in self.blocks.into_iter() {
+        for y in arr.to_nested().into_iter() {
             let mut x_arr = Vec::with_capacity(y.len());
             for x in y.into_iter() {
                 x_arr.push(ZAxis { b: x });
@@ -40,7 +50,13 @@ impl Into<JsonModel> for ArrayModel {
             y_arr.push(XAxis { b: x_arr });
         }
 
-        JsonModel { b: y_arr }
+        let GridTransform { origin, voxel_size } = arr.transform;
+        JsonModel {
+            b: y_arr,
+            o: [origin.x, origin.y, origin.z],
+            s: [voxel_size.x, voxel_size.y, voxel_size.z],
+            p: arr.registry,
+        }
     }
 }
 
@@ -57,8 +73,6 @@ pub fn arr_2_json<P>(path: P, arr: ArrayModel) -> std::io::Result<()>
     Ok(())
 }
 
-pub struct Blocks(pub Vec<String>);
-
 pub type Block = i16;
 
 type A2B = float;
@@ -137,114 +151,411 @@ impl<'a> TriangularPlane<'a> {
     }
 }
 
-fn make_range(min: float, max: float, resolution: float) -> Range<usize> {
-    let min = (min * resolution).round() as usize;
-    let max = (max * resolution).round() as usize + 1;
+/// Sample indices covering `min..=max` at `resolution` samples per voxel, clamped to `0..limit` voxels.
+fn make_range(min: float, max: float, resolution: float, limit: uint) -> Range<i64> {
+    let min = ((min * resolution).round() as i64).max(0);
+    let max = ((max * resolution).round() as i64 + 1).min((limit as float * resolution).ceil() as i64);
     min..max
 }
 
-fn bounds_to_range(bounds: (Vec3, Vec3), resolution: float) -> (Range<usize>, Range<usize>, Range<usize>) {
-    (
-        make_range(bounds.0.x, bounds.1.x, resolution),
-        make_range(bounds.0.y, bounds.1.y, resolution),
-        make_range(bounds.0.z, bounds.1.z, resolution),
-    )
+/// Voxel index containing `v`, or `None` if it lies outside of `0..limit`.
+pub(crate) fn voxel_index(v: float, limit: uint) -> Option<usize> {
+    let v = v.floor();
+    if v >= 0.0 && v < limit as float {
+        Some(v as usize)
+    } else {
+        None
+    }
 }
 
 pub type CoordXZ = (uint, uint);
 pub type CoordXYZ = (uint, uint, uint);
 
-/// Blocks are stored as blocks[y][x][z]
-#[derive(Debug)]
+/// Blocks are stored flat in y, x, z order, i.e. `blocks[(y * dims.x + x) * dims.z + z]`, so a
+/// Z row and a Y layer are each contiguous.
+#[derive(Debug, Clone)]
 pub struct ArrayModel {
-    pub blocks: Vec<Vec<Vec<Block>>>,
+    pub blocks: Vec<Block>,
     pub dims: CoordXYZ,
     pub resolution: float,
+    pub transform: GridTransform,
+    pub registry: BlockRegistry,
 }
 
 impl ArrayModel {
     pub fn new(dims: CoordXYZ, resolution: float) -> Self {
-        let mut y_arr = Vec::with_capacity(dims.1 as usize);
-        for _y in 0..dims.1 {
-            let mut x_arr = Vec::with_capacity(dims.0 as usize);
-            for _x in 0..dims.0 {
-                let mut z_arr = Vec::with_capacity(dims.2 as usize);
-                for _z in 0..dims.2 {
-                    z_arr.push(0);
-                }
-                x_arr.push(z_arr);
-            }
-            y_arr.push(x_arr);
+        Self::with_transform(dims, GridTransform::default(), resolution)
+    }
+
+    pub fn with_transform(dims: CoordXYZ, transform: GridTransform, resolution: float) -> Self {
+        let len = dims.0 as usize * dims.1 as usize * dims.2 as usize;
+        Self { blocks: vec![0; len], dims, resolution, transform, registry: BlockRegistry::default() }
+    }
+
+    /// Builds a grid from the nested blocks[y][x][z] layout.
+    pub fn from_nested(blocks: Vec<Vec<Vec<Block>>>, resolution: float) -> Result<Self> {
+        let dims = (
+            blocks.first().map_or(0, |y| y.len()),
+            blocks.len(),
+            blocks.first().and_then(|y| y.first()).map_or(0, |x| x.len()),
+        );
+        let ragged = blocks.iter().any(|y| y.len() != dims.0 || y.iter().any(|x| x.len() != dims.2));
+        let too_large = [dims.0, dims.1, dims.2].iter().any(|&d| d > uint::MAX as usize);
+        if ragged || too_large {
+            return Err(Error::InvalidNestedArray);
+        }
+
+        let dims = (dims.0 as uint, dims.1 as uint, dims.2 as uint);
+        let blocks = blocks.into_iter().flatten().flatten().collect();
+        Ok(Self {
+            blocks,
+            dims,
+            resolution,
+            transform: GridTransform::default(),
+            registry: BlockRegistry::default(),
+        })
+    }
+
+    /// Copies the blocks into the nested blocks[y][x][z] layout.
+    pub fn to_nested(&self) -> Vec<Vec<Vec<Block>>> {
+        let (dx, dz) = (self.dims.0 as usize, self.dims.2 as usize);
+        if dx == 0 || dz == 0 {
+            return (0..self.dims.1).map(|_| vec![vec![]; dx]).collect();
+        }
+        self.blocks
+            .chunks(dx * dz)
+            .map(|layer| layer.chunks(dz).map(|row| row.to_vec()).collect())
+            .collect()
+    }
+
+    /// Voxel containing a world position, or `None` if it lies outside of the grid.
+    pub fn world_to_voxel(&self, p: &Vec3) -> Option<(usize, usize, usize)> {
+        self.transform.world_to_voxel_coord(p, self.dims)
+    }
+
+    /// World position of the minimum corner of a voxel.
+    /// c_xyz: (x, y, z)
+    pub fn voxel_to_world(&self, c_xyz: (usize, usize, usize)) -> Vec3 {
+        self.transform.voxel_to_world(c_xyz)
+    }
+
+    /// Index into `blocks`, or `None` if out of bounds.
+    /// c_xyz: (x, y, z)
+    pub fn index(&self, c_xyz: (usize, usize, usize)) -> Option<usize> {
+        if !self.in_bounds(c_xyz) {
+            return None;
         }
-        Self { blocks: y_arr, dims, resolution }
+        Some((c_xyz.1 * self.dims.0 as usize + c_xyz.0) * self.dims.2 as usize + c_xyz.2)
+    }
+
+    /// Coordinate (x, y, z) of an index into `blocks`.
+    pub fn coord(&self, i: usize) -> (usize, usize, usize) {
+        let (dx, dz) = (self.dims.0 as usize, self.dims.2 as usize);
+        (i / dz % dx, i / (dx * dz), i % dz)
     }
 
     /// c_xyz: (x, y, z)
-    pub fn get(&self, c_xyz: (usize, usize, usize)) -> Block {
-        self.blocks[c_xyz.1][c_xyz.0][c_xyz.2]
+    pub fn get(&self, c_xyz: (usize, usize, usize)) -> Option<Block> {
+        self.index(c_xyz).map(|i| self.blocks[i])
     }
 
+    /// Returns the previous block, or `None` if out of bounds.
     /// c_xyz: (x, y, z)
-    pub fn set(&mut self, c_xyz: (usize, usize, usize), val: Block) {
-        self.blocks[c_xyz.1][c_xyz.0][c_xyz.2] = val;
+    pub fn set(&mut self, c_xyz: (usize, usize, usize), val: Block) -> Option<Block> {
+        let i = self.index(c_xyz)?;
+        Some(std::mem::replace(&mut self.blocks[i], val))
+    }
+
+    /// The blocks along Z at (x, y).
+    pub fn row(&self, x: usize, y: usize) -> Option<&[Block]> {
+        let start = self.index((x, y, 0))?;
+        Some(&self.blocks[start..start + self.dims.2 as usize])
+    }
+
+    pub fn row_mut(&mut self, x: usize, y: usize) -> Option<&mut [Block]> {
+        let start = self.index((x, y, 0))?;
+        let len = self.dims.2 as usize;
+        Some(&mut self.blocks[start..start + len])
+    }
+
+    /// The X * Z blocks of layer y, stored as `layer[x * dims.z + z]`.
+    pub fn layer(&self, y: usize) -> Option<&[Block]> {
+        let len = self.dims.0 as usize * self.dims.2 as usize;
+        if y >= self.dims.1 as usize {
+            return None;
+        }
+        Some(&self.blocks[y * len..(y + 1) * len])
+    }
+
+    pub fn layer_mut(&mut self, y: usize) -> Option<&mut [Block]> {
+        let len = self.dims.0 as usize * self.dims.2 as usize;
+        if y >= self.dims.1 as usize {
+            return None;
+        }
+        Some(&mut self.blocks[y * len..(y + 1) * len])
     }
 }
 
-const DEFAULT_TEXTURE_ID: Block = -1;
+impl VoxelGrid for ArrayModel {
+    fn dims(&self) -> CoordXYZ {
+        self.dims
+    }
+
+    fn resolution(&self) -> float {
+        self.resolution
+    }
+
+    fn transform(&self) -> &GridTransform {
+        &self.transform
+    }
+
+    fn registry(&self) -> &BlockRegistry {
+        &self.registry
+    }
+
+    fn get(&self, c_xyz: (usize, usize, usize)) -> Option<Block> {
+        ArrayModel::get(self, c_xyz)
+    }
+
+    fn set(&mut self, c_xyz: (usize, usize, usize), val: Block) -> Option<Block> {
+        ArrayModel::set(self, c_xyz, val)
+    }
+
+    fn iter_filled(&self) -> Box<dyn Iterator<Item = ((usize, usize, usize), Block)> + '_> {
+        Box::new(ArrayModel::iter_filled(self))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct VoxelizeReport {
+    pub triangles: usize,
+    /// Triangles which were partially or entirely outside of the grid.
+    pub clipped_triangles: usize,
+}
 
 /// Resolution up samples the model. Increase this if there are many "holes" in the resulting array.
+///
+/// Expects the model to already be scaled and aligned to the origin, see `model_2_arr_with_origin`
+/// for models in arbitrary model space. Anything outside of `dims` is clipped.
 pub fn model_2_arr(
     model: Model,
     dims: CoordXYZ,
     resolution: float,
 ) -> ArrayModel {
-    let vertices = model.vertices.0;
+    let (array_model, _report) = model_2_arr_with_origin(&model, dims, Vec3::ZERO, 1.0, resolution)
+        .expect("Invalid face!");
+    array_model
+}
+
+/// Voxelizes a model in model space into a grid of `dims` voxels, where voxel (0, 0, 0) starts at
+/// `origin` and every voxel is `voxel_size` model units wide.
+///
+/// Triangles (or parts of triangles) outside of the grid are clipped and counted in the report.
+pub fn model_2_arr_with_origin(
+    model: &Model,
+    dims: CoordXYZ,
+    origin: Vec3,
+    voxel_size: float,
+    resolution: float,
+) -> Result<(ArrayModel, VoxelizeReport)> {
+    model_2_arr_with_transform(model, dims, GridTransform::uniform(origin, voxel_size), resolution)
+}
 
-    let mut array_model = ArrayModel::new(dims, resolution);
+/// Same as `model_2_arr_with_origin`, but with a per-axis voxel size. The transform is stored on
+/// the resulting `ArrayModel`.
+pub fn model_2_arr_with_transform(
+    model: &Model,
+    dims: CoordXYZ,
+    transform: GridTransform,
+    resolution: float,
+) -> Result<(ArrayModel, VoxelizeReport)> {
+    let mut array_model = ArrayModel::with_transform(dims, transform, resolution);
+    let report = model_2_arr_into(&mut array_model, model, DEFAULT_BLOCK)?;
+    Ok((array_model, report))
+}
 
-    for i in 0..model.faces.0.len() {
-        let face = &model.faces.0[i];
-        if face.len() < 3 {
-            panic!("Invalid face!");
-        }
-        if face.len() > 3 {
-            unimplemented!("Cannot handle polygons yet!");
+/// Voxelizes a model into an existing grid using the grid's transform and resolution, writing
+/// `block` for every filled voxel. Voxels which are already set are overwritten.
+///
+/// Works with any `VoxelGrid`, e.g. a `ChunkedModel` for grids too large to store densely.
+pub fn model_2_arr_into<G: VoxelGrid>(
+    array_model: &mut G,
+    model: &Model,
+    block: Block,
+) -> Result<VoxelizeReport> {
+    let vertices = model.vertices.0
+        .iter()
+        .map(|v| array_model.transform().world_to_voxel(v))
+        .collect::<Vec<Vec3>>();
+
+    let dims = array_model.dims();
+    let resolution = array_model.resolution();
+    let mut report = VoxelizeReport::default();
+
+    for (i, face) in model.faces.0.iter().enumerate() {
+        if let Some(&index) = face.iter().find(|&&index| index >= vertices.len()) {
+            return Err(Error::InvalidFace { face: i, index });
         }
         let plane = TriangularPlane::from_plane(
             &vertices[face[0]],
             &vertices[face[1]],
             &vertices[face[2]],
         );
+        report.triangles += 1;
 
         let bounds = plane.bounds();
-        let (x_range, y_range, z_range) = bounds_to_range(bounds, resolution);
+        let outside = bounds.0.x < 0.0 || bounds.0.y < 0.0 || bounds.0.z < 0.0
+            || bounds.1.x >= dims.0 as float || bounds.1.y >= dims.1 as float || bounds.1.z >= dims.2 as float;
+        if outside {
+            report.clipped_triangles += 1;
+        }
+
+        let x_range = make_range(bounds.0.x, bounds.1.x, resolution, dims.0);
+        let y_range = make_range(bounds.0.y, bounds.1.y, resolution, dims.1);
         let fills_z = plane.fills_z();
-        for x in x_range.clone() {
+        for x in x_range {
             for y in y_range.clone() {
                 let p = Vec2::new(x as float / resolution, y as float / resolution);
                 let weights = plane.calc_weights(&p);
 
                 if TriangularPlane::weights_within_plane(weights) {
-                    let x = x / resolution as usize;
-                    let y = y / resolution as usize;
-
-                    const BLOCK: Block = DEFAULT_TEXTURE_ID;
+                    let (x, y) = match (voxel_index(p.x, dims.0), voxel_index(p.y, dims.1)) {
+                        (Some(x), Some(y)) => (x, y),
+                        _ => continue,
+                    };
 
+                    // x and y sample at whole coordinates, so z rounds to the nearest one rather
+                    // than flooring, as `model_2_arr` always has.
                     if fills_z {
-                        for z in z_range.clone() {
-                            let z = (z as float / resolution).round() as usize;
-                            array_model.set((x, y, z), BLOCK);
-                        }
-                    } else if !fills_z {
-                        let z = plane.calculate_z(&p);
-                        let z = z.round() as usize;
-                        if z < array_model.dims.2.into() {
-                            array_model.set((x, y, z), BLOCK);
+                        let z_min = (bounds.0.z.round() as i64).max(0);
+                        let z_max = (bounds.1.z.round() as i64).min(dims.2 as i64 - 1);
+                        for z in z_min..=z_max {
+                            array_model.set((x, y, z as usize), block);
                         }
+                    } else if let Some(z) = voxel_index(plane.calculate_z(&p).round(), dims.2) {
+                        array_model.set((x, y, z), block);
                     }
                 }
             }
         }
     }
-    array_model
-}
\ No newline at end of file
+    Ok(report)
+}
+
+/// Voxelizes all models of a scene (e.g. every object of a multi-part OBJ) into one grid.
+///
+/// The models are fitted together by their shared bounding box, so parts keep their relative
+/// placement. If `tag_objects` is set, voxels are set to the index of their source model + 1
+/// instead of the default block and every model is registered as `object_<id>`. Later models
+/// overwrite earlier ones where they overlap.
+pub fn scene_2_arr(
+    models: &[Model],
+    dims: CoordXYZ,
+    opts: &FitOptions,
+    resolution: float,
+    tag_objects: bool,
+) -> Result<(ArrayModel, VoxelizeReport)> {
+    let transform = fit::fit_transform(fit::models_dims(models)?, dims, opts)?;
+    let mut array_model = ArrayModel::with_transform(dims, transform, resolution);
+    let mut report = VoxelizeReport::default();
+
+    for (i, model) in models.iter().enumerate() {
+        let block = if tag_objects {
+            let block = i as Block + 1;
+            array_model.registry.insert(block, BlockDef::new(&format!("object_{}", block)));
+            block
+        } else {
+            DEFAULT_BLOCK
+        };
+        let model_report = model_2_arr_into(&mut array_model, model, block)?;
+        report.triangles += model_report.triangles;
+        report.clipped_triangles += model_report.clipped_triangles;
+    }
+    Ok((array_model, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Faces, Points};
+
+    fn model(vertices: Vec<Vec3>) -> Model {
+        let faces = (0..vertices.len() / 3).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect();
+        Model::new(Points(vertices), Faces(faces))
+    }
+
+    #[test]
+    fn vertical_triangle_far_outside_z_is_clamped() {
+        // Normal along x, so the triangle fills along z.
+        let m = model(vec![
+            Vec3::new(1.5, 0.0, -1e9),
+            Vec3::new(1.5, 3.5, -1e9),
+            Vec3::new(1.5, 0.0, 1e9),
+        ]);
+        let start = std::time::Instant::now();
+        let (arr, report) = model_2_arr_with_transform(&m, (4, 4, 4), GridTransform::default(), 1.0).unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(report.clipped_triangles, 1);
+        assert!((0..4).all(|z| arr.get((2, 0, z)) == Some(DEFAULT_BLOCK)));
+    }
+
+    #[test]
+    fn voxelizes_inside_grid() {
+        let m = model(vec![
+            Vec3::new(0.0, 0.0, 1.25),
+            Vec3::new(3.5, 0.0, 1.25),
+            Vec3::new(0.0, 3.5, 1.25),
+        ]);
+        let (arr, report) = model_2_arr_with_transform(&m, (4, 4, 4), GridTransform::default(), 1.0).unwrap();
+        assert_eq!((report.triangles, report.clipped_triangles), (1, 0));
+        assert!(arr.iter_filled().count() > 0);
+        assert!(arr.iter_filled().all(|((_, _, z), b)| z == 1 && b == DEFAULT_BLOCK));
+    }
+
+    #[test]
+    fn z_rounds_to_nearest_voxel() {
+        let flat = |z: float| model(vec![
+            Vec3::new(0.0, 0.0, z),
+            Vec3::new(3.0, 0.0, z),
+            Vec3::new(0.0, 3.0, z),
+        ]);
+        for (z, expected) in [(1.4, 1), (1.6, 2), (0.5, 1)] {
+            let arr = model_2_arr(flat(z), (4, 4, 4), 1.0);
+            assert!(arr.iter_filled().all(|((_, _, z), _)| z == expected));
+            assert!(arr.iter_filled().count() > 0);
+        }
+    }
+
+    #[test]
+    fn scene_parts_keep_their_offset() {
+        let part = |dx: float| model(vec![
+            Vec3::new(dx, 0.0, 0.0),
+            Vec3::new(dx + 2.0, 0.0, 1.0),
+            Vec3::new(dx, 2.0, 2.0),
+        ]);
+        let (arr, report) = scene_2_arr(&[part(0.0), part(6.0)], (16, 16, 16), &FitOptions::default(), 1.0, true).unwrap();
+        assert_eq!((report.triangles, report.clipped_triangles), (2, 0));
+        assert_eq!(arr.registry.get(1).map(|def| def.name.as_str()), Some("object_1"));
+        assert_eq!(arr.registry.get(2).map(|def| def.name.as_str()), Some("object_2"));
+
+        let min_corner = |block| arr.iter_block(block).fold((usize::MAX, usize::MAX, usize::MAX), |m, (x, y, z)| {
+            (m.0.min(x), m.1.min(y), m.2.min(z))
+        });
+        let (first, second) = (min_corner(1), min_corner(2));
+        assert!(first.0 != usize::MAX && second.0 != usize::MAX);
+        assert!(arr.iter_filled().all(|(_, b)| b == 1 || b == 2));
+        assert_eq!((first.1, first.2), (second.1, second.2));
+        // 6 model units apart along x, up to where the triangle edges land between voxels.
+        let shift = 6.0 / arr.transform.voxel_size.x;
+        assert!(((second.0 - first.0) as float - shift).abs() <= 1.0);
+    }
+
+    #[test]
+    fn nested_round_trip() {
+        let mut arr = ArrayModel::new((2, 3, 4), 1.0);
+        arr.set((1, 2, 3), 5);
+        let nested = arr.to_nested();
+        assert_eq!(ArrayModel::from_nested(nested, 1.0).unwrap().blocks, arr.blocks);
+        assert!(ArrayModel::from_nested(vec![vec![vec![0, 0], vec![0]]], 1.0).is_err());
+    }
+}
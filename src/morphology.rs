@@ -0,0 +1,138 @@
+use crate::grid::{offset_coord, Neighbourhood};
+use crate::model2arr::{ArrayModel, Block};
+
+/// Grows the model by one voxel per iteration. An air voxel next to filled voxels becomes the most
+/// common of the neighbouring blocks, face neighbours win ties.
+pub fn dilate(arr: &ArrayModel, neighbourhood: Neighbourhood, iterations: usize) -> ArrayModel {
+    let offsets = neighbourhood.offsets();
+    let mut current = arr.clone();
+    for _ in 0..iterations {
+        let mut next = current.clone();
+        let mut counts: Vec<(Block, usize)> = Vec::with_capacity(offsets.len());
+        for i in 0..current.blocks.len() {
+            let c_xyz = current.coord(i);
+            if current.blocks[i] != 0 {
+                continue;
+            }
+            counts.clear();
+            for &d in offsets.iter() {
+                let b = offset_coord(c_xyz, d, current.dims).map_or(0, |c| current.get(c).unwrap_or(0));
+                if b == 0 {
+                    continue;
+                }
+                match counts.iter_mut().find(|(block, _)| *block == b) {
+                    Some((_, n)) => *n += 1,
+                    None => counts.push((b, 1)),
+                }
+            }
+            // max_by_key returns the last maximum, reverse so the closest neighbour wins ties.
+            if let Some(&(b, _)) = counts.iter().rev().max_by_key(|(_, n)| *n) {
+                next.blocks[i] = b;
+            }
+        }
+        current = next;
+    }
+    current
+}
+
+/// Shrinks the model by one voxel per iteration. A filled voxel next to air becomes air, voxels
+/// outside of the grid count as air.
+pub fn erode(arr: &ArrayModel, neighbourhood: Neighbourhood, iterations: usize) -> ArrayModel {
+    erode_with(arr, neighbourhood, iterations, false)
+}
+
+/// `outside_filled` treats voxels outside of the grid as filled, so the grid edge is not eroded.
+fn erode_with(arr: &ArrayModel, neighbourhood: Neighbourhood, iterations: usize, outside_filled: bool) -> ArrayModel {
+    let offsets = neighbourhood.offsets();
+    let mut current = arr.clone();
+    for _ in 0..iterations {
+        let mut next = current.clone();
+        for i in 0..current.blocks.len() {
+            let c_xyz = current.coord(i);
+            if current.blocks[i] == 0 {
+                continue;
+            }
+            let exposed = offsets.iter().any(|&d| {
+                match offset_coord(c_xyz, d, current.dims) {
+                    Some(c) => current.get(c) == Some(0),
+                    None => !outside_filled,
+                }
+            });
+            if exposed {
+                next.blocks[i] = 0;
+            }
+        }
+        current = next;
+    }
+    current
+}
+
+/// Erode then dilate, removes thin walls and small protrusions.
+pub fn open(arr: &ArrayModel, neighbourhood: Neighbourhood, iterations: usize) -> ArrayModel {
+    dilate(&erode(arr, neighbourhood, iterations), neighbourhood, iterations)
+}
+
+/// Dilate then erode, closes small gaps and holes. Never removes voxels of the original model, the
+/// erosion treats the outside of the grid as filled so models touching the grid edge are kept.
+pub fn close(arr: &ArrayModel, neighbourhood: Neighbourhood, iterations: usize) -> ArrayModel {
+    erode_with(&dilate(arr, neighbourhood, iterations), neighbourhood, iterations, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor() -> ArrayModel {
+        let mut arr = ArrayModel::new((5, 3, 5), 1.0);
+        for x in 0..5 {
+            for z in 0..5 {
+                arr.set((x, 0, z), 1);
+            }
+        }
+        arr
+    }
+
+    fn contains(outer: &ArrayModel, inner: &ArrayModel) -> bool {
+        inner.blocks.iter().zip(outer.blocks.iter()).all(|(&i, &o)| i == 0 || i == o)
+    }
+
+    #[test]
+    fn close_keeps_floor_on_grid_edge() {
+        let arr = floor();
+        for nb in [Neighbourhood::Six, Neighbourhood::Eighteen, Neighbourhood::TwentySix] {
+            for iterations in 1..3 {
+                let closed = close(&arr, nb, iterations);
+                assert!(contains(&closed, &arr), "{:?} {}", nb, iterations);
+            }
+        }
+    }
+
+    #[test]
+    fn close_fills_gap() {
+        // Air pocket inside a two layer slab.
+        let mut arr = floor();
+        for x in 0..5 {
+            for z in 0..5 {
+                arr.set((x, 1, z), 2);
+            }
+        }
+        arr.set((2, 0, 2), 0);
+        let closed = close(&arr, Neighbourhood::Six, 1);
+        assert!(contains(&closed, &arr));
+        assert_eq!(closed.get((2, 0, 2)), Some(1));
+    }
+
+    #[test]
+    fn erode_treats_outside_as_air() {
+        let eroded = erode(&floor(), Neighbourhood::Six, 1);
+        assert_eq!(eroded.iter_filled().count(), 0);
+    }
+
+    #[test]
+    fn dilate_grows_by_one() {
+        let mut arr = ArrayModel::new((3, 3, 3), 1.0);
+        arr.set((1, 1, 1), 4);
+        let dilated = dilate(&arr, Neighbourhood::Six, 1);
+        assert_eq!(dilated.iter_block(4).count(), 7);
+    }
+}
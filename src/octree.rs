@@ -0,0 +1,454 @@
+use crate::grid::{GridTransform, VoxelGrid};
+use crate::registry::BlockRegistry;
+use crate::model2arr::{ArrayModel, Block, CoordXYZ};
+use crate::vec3::Vec3;
+use super::float;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    /// Every voxel within the node is the same block.
+    Leaf(Block),
+    /// Children are ordered by `(x, y, z)` bits, i.e. `x | y << 1 | z << 2`.
+    Branch(Box<[Node; 8]>),
+}
+
+impl Node {
+    fn child_index(c_xyz: (usize, usize, usize), half: usize) -> usize {
+        (c_xyz.0 & half != 0) as usize
+            | ((c_xyz.1 & half != 0) as usize) << 1
+            | ((c_xyz.2 & half != 0) as usize) << 2
+    }
+
+    fn child_min(min: (usize, usize, usize), i: usize, half: usize) -> (usize, usize, usize) {
+        (
+            min.0 + (i & 1) * half,
+            min.1 + (i >> 1 & 1) * half,
+            min.2 + (i >> 2 & 1) * half,
+        )
+    }
+
+    /// Collapses uniform subtrees bottom up.
+    fn collapse(&mut self) {
+        if let Node::Branch(children) = self {
+            for child in children.iter_mut() {
+                child.collapse();
+            }
+        }
+        self.collapse_shallow();
+    }
+
+    /// Replaces a branch whose children are all the same leaf by that leaf.
+    fn collapse_shallow(&mut self) {
+        if let Node::Branch(children) = self {
+            if let Node::Leaf(b) = children[0] {
+                if children.iter().all(|c| *c == Node::Leaf(b)) {
+                    *self = Node::Leaf(b);
+                }
+            }
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Branch(children) => 1 + children.iter().map(|c| c.count()).sum::<usize>(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RayHit {
+    /// c_xyz: (x, y, z)
+    pub c_xyz: (usize, usize, usize),
+    pub block: Block,
+    /// Distance along the normalized ray direction, in voxels.
+    pub distance: float,
+}
+
+/// Sparse voxel octree, uniform regions (e.g. air) are stored as a single leaf.
+///
+/// The tree covers a cube with a power of two side length, voxels outside of `dims` are air.
+#[derive(Debug, Clone)]
+pub struct Octree {
+    root: Node,
+    size: usize,
+    pub dims: CoordXYZ,
+    pub resolution: float,
+    pub transform: GridTransform,
+    pub registry: BlockRegistry,
+}
+
+impl Octree {
+    pub fn new(dims: CoordXYZ, resolution: float) -> Self {
+        Self::with_transform(dims, GridTransform::default(), resolution)
+    }
+
+    pub fn with_transform(dims: CoordXYZ, transform: GridTransform, resolution: float) -> Self {
+        let size = (dims.0.max(dims.1).max(dims.2) as usize).next_power_of_two();
+        Self { root: Node::Leaf(0), size, dims, resolution, transform, registry: BlockRegistry::default() }
+    }
+
+    pub fn from_array(arr: &ArrayModel) -> Self {
+        let mut tree = Self::with_transform(arr.dims, arr.transform.clone(), arr.resolution);
+        tree.registry = arr.registry.clone();
+        tree.root = Self::build(arr, (0, 0, 0), tree.size);
+        tree
+    }
+
+    fn build(arr: &ArrayModel, min: (usize, usize, usize), size: usize) -> Node {
+        if size == 1 {
+            return Node::Leaf(arr.get(min).unwrap_or(0));
+        }
+        let half = size / 2;
+        let children: [Node; 8] = std::array::from_fn(|i| Self::build(arr, Node::child_min(min, i, half), half));
+        let mut node = Node::Branch(Box::new(children));
+        node.collapse_shallow();
+        node
+    }
+
+    pub fn to_array(&self) -> ArrayModel {
+        let mut arr = ArrayModel::with_transform(self.dims, self.transform.clone(), self.resolution);
+        arr.registry = self.registry.clone();
+        for (c_xyz, b) in self.iter_range((0, 0, 0), (self.dims.0 as usize, self.dims.1 as usize, self.dims.2 as usize)) {
+            arr.set(c_xyz, b);
+        }
+        arr
+    }
+
+    /// Number of nodes in the tree, including branches.
+    pub fn node_count(&self) -> usize {
+        self.root.count()
+    }
+
+    /// Collapses every subtree whose voxels are all the same block. `set` keeps the tree collapsed,
+    /// this is only needed if the tree was built some other way.
+    pub fn collapse(&mut self) {
+        self.root.collapse();
+    }
+
+    /// Leaf containing the voxel, with the leaf's min corner and size.
+    fn leaf(&self, c_xyz: (usize, usize, usize)) -> (Block, (usize, usize, usize), usize) {
+        let mut node = &self.root;
+        let mut min = (0, 0, 0);
+        let mut size = self.size;
+        loop {
+            match node {
+                Node::Leaf(b) => return (*b, min, size),
+                Node::Branch(children) => {
+                    size /= 2;
+                    let i = Node::child_index(c_xyz, size);
+                    min = Node::child_min(min, i, size);
+                    node = &children[i];
+                }
+            }
+        }
+    }
+
+    /// c_xyz: (x, y, z)
+    pub fn get(&self, c_xyz: (usize, usize, usize)) -> Option<Block> {
+        if !self.in_bounds(c_xyz) {
+            return None;
+        }
+        Some(self.leaf(c_xyz).0)
+    }
+
+    /// Returns the previous block, or `None` if out of bounds.
+    /// c_xyz: (x, y, z)
+    pub fn set(&mut self, c_xyz: (usize, usize, usize), val: Block) -> Option<Block> {
+        if !self.in_bounds(c_xyz) {
+            return None;
+        }
+        Some(Self::set_node(&mut self.root, c_xyz, self.size, val))
+    }
+
+    fn set_node(node: &mut Node, c_xyz: (usize, usize, usize), size: usize, val: Block) -> Block {
+        if let Node::Leaf(b) = *node {
+            if b == val || size == 1 {
+                *node = Node::Leaf(val);
+                return b;
+            }
+            *node = Node::Branch(Box::new(std::array::from_fn(|_| Node::Leaf(b))));
+        }
+        let prev = match node {
+            Node::Branch(children) => {
+                let half = size / 2;
+                Self::set_node(&mut children[Node::child_index(c_xyz, half)], c_xyz, half, val)
+            }
+            Node::Leaf(_) => unreachable!(),
+        };
+        node.collapse_shallow();
+        prev
+    }
+
+    /// All non air voxels with `min <= c_xyz < max`, skipping empty subtrees. Voxels are produced
+    /// lazily while walking the tree.
+    pub fn iter_range(
+        &self,
+        min: (usize, usize, usize),
+        max: (usize, usize, usize),
+    ) -> impl Iterator<Item = ((usize, usize, usize), Block)> + '_ {
+        let max = (
+            max.0.min(self.dims.0 as usize),
+            max.1.min(self.dims.1 as usize),
+            max.2.min(self.dims.2 as usize),
+        );
+        RangeIter { stack: vec![(&self.root, (0, 0, 0), self.size)], min, max, leaf: None }
+    }
+
+    /// Casts a ray in grid space (1 unit per voxel) and returns the first non air voxel within
+    /// `max_dist`. Empty subtrees are skipped in one step. A zero `dir` never hits anything.
+    pub fn raycast(&self, origin: &Vec3, dir: &Vec3, max_dist: float) -> Option<RayHit> {
+        if [dir.x, dir.y, dir.z].iter().all(|&v| v == 0.0) || ![dir.x, dir.y, dir.z].iter().all(|v| v.is_finite()) {
+            return None;
+        }
+        let dir = dir.clone().normalize();
+        let o = [origin.x, origin.y, origin.z];
+        let d = [dir.x, dir.y, dir.z];
+        let dims = [self.dims.0 as float, self.dims.1 as float, self.dims.2 as float];
+        let cells = [self.dims.0 as i64, self.dims.1 as i64, self.dims.2 as i64];
+
+        // Clip the ray to the grid bounds.
+        let (mut t, mut t_end) = (0.0 as float, max_dist);
+        for a in 0..3 {
+            if d[a] == 0.0 {
+                if o[a] < 0.0 || o[a] >= dims[a] {
+                    return None;
+                }
+            } else {
+                let t0 = (0.0 - o[a]) / d[a];
+                let t1 = (dims[a] - o[a]) / d[a];
+                t = t.max(t0.min(t1));
+                t_end = t_end.min(t0.max(t1));
+            }
+        }
+        if t > t_end {
+            return None;
+        }
+
+        // Walk the leaves on integer coordinates, so the ray always moves on to the next leaf no
+        // matter how far `t` is from the origin.
+        let mut c = [0i64; 3];
+        for a in 0..3 {
+            c[a] = ((o[a] + d[a] * t).floor() as i64).clamp(0, cells[a] - 1);
+        }
+        loop {
+            let c_xyz = (c[0] as usize, c[1] as usize, c[2] as usize);
+            let (block, min, size) = self.leaf(c_xyz);
+            if block != 0 {
+                return Some(RayHit { c_xyz, block, distance: t });
+            }
+
+            // Where the ray leaves this leaf, and through which faces.
+            let lo = [min.0 as i64, min.1 as i64, min.2 as i64];
+            let hi = [lo[0] + size as i64, lo[1] + size as i64, lo[2] + size as i64];
+            let mut exits = [float::INFINITY; 3];
+            for a in 0..3 {
+                if d[a] > 0.0 {
+                    exits[a] = (hi[a] as float - o[a]) / d[a];
+                } else if d[a] < 0.0 {
+                    exits[a] = (lo[a] as float - o[a]) / d[a];
+                }
+            }
+            let t_exit = exits.iter().copied().fold(float::INFINITY, float::min);
+            t = t.max(t_exit);
+            if t > t_end {
+                return None;
+            }
+
+            for a in 0..3 {
+                if exits[a] == t_exit {
+                    c[a] = if d[a] > 0.0 { hi[a] } else { lo[a] - 1 };
+                } else {
+                    // Stay inside the leaf along the other axes, and never move against the ray.
+                    let p = ((o[a] + d[a] * t_exit).floor() as i64).clamp(lo[a], hi[a] - 1);
+                    c[a] = if d[a] > 0.0 { p.max(c[a]) } else { p.min(c[a]) };
+                }
+            }
+            if (0..3).any(|a| c[a] < 0 || c[a] >= cells[a]) {
+                return None;
+            }
+        }
+    }
+}
+
+/// Depth first walk of the leaves overlapping `min..max`, with the pending subtrees on a stack.
+struct RangeIter<'a> {
+    /// (node, node min corner, node size)
+    stack: Vec<(&'a Node, (usize, usize, usize), usize)>,
+    min: (usize, usize, usize),
+    max: (usize, usize, usize),
+    leaf: Option<LeafVoxels>,
+}
+
+/// Voxels of a leaf still to be emitted, `lo..hi` is the part of the leaf inside the range.
+struct LeafVoxels {
+    block: Block,
+    lo: (usize, usize, usize),
+    hi: (usize, usize, usize),
+    next: (usize, usize, usize),
+}
+
+impl<'a> Iterator for RangeIter<'a> {
+    type Item = ((usize, usize, usize), Block);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(leaf) = self.leaf.as_mut() {
+                let c = &mut leaf.next;
+                if c.0 < leaf.hi.0 {
+                    let item = (*c, leaf.block);
+                    c.2 += 1;
+                    if c.2 == leaf.hi.2 {
+                        c.2 = leaf.lo.2;
+                        c.1 += 1;
+                        if c.1 == leaf.hi.1 {
+                            c.1 = leaf.lo.1;
+                            c.0 += 1;
+                        }
+                    }
+                    return Some(item);
+                }
+                self.leaf = None;
+            }
+
+            let (node, node_min, size) = self.stack.pop()?;
+            let (min, max) = (self.min, self.max);
+            let lo = (node_min.0.max(min.0), node_min.1.max(min.1), node_min.2.max(min.2));
+            let hi = ((node_min.0 + size).min(max.0), (node_min.1 + size).min(max.1), (node_min.2 + size).min(max.2));
+            if lo.0 >= hi.0 || lo.1 >= hi.1 || lo.2 >= hi.2 {
+                continue;
+            }
+            match node {
+                Node::Leaf(0) => {}
+                Node::Leaf(b) => self.leaf = Some(LeafVoxels { block: *b, lo, hi, next: lo }),
+                Node::Branch(children) => {
+                    let half = size / 2;
+                    // Reversed, so children are visited in index order.
+                    for (i, child) in children.iter().enumerate().rev() {
+                        self.stack.push((child, Node::child_min(node_min, i, half), half));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl VoxelGrid for Octree {
+    fn dims(&self) -> CoordXYZ {
+        self.dims
+    }
+
+    fn resolution(&self) -> float {
+        self.resolution
+    }
+
+    fn transform(&self) -> &GridTransform {
+        &self.transform
+    }
+
+    fn registry(&self) -> &BlockRegistry {
+        &self.registry
+    }
+
+    fn get(&self, c_xyz: (usize, usize, usize)) -> Option<Block> {
+        Octree::get(self, c_xyz)
+    }
+
+    fn set(&mut self, c_xyz: (usize, usize, usize), val: Block) -> Option<Block> {
+        Octree::set(self, c_xyz, val)
+    }
+
+    fn iter_filled(&self) -> Box<dyn Iterator<Item = ((usize, usize, usize), Block)> + '_> {
+        let dims = self.dims;
+        Box::new(self.iter_range((0, 0, 0), (dims.0 as usize, dims.1 as usize, dims.2 as usize)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagonal_hit(n: usize) {
+        let mut tree = Octree::new((n as _, n as _, n as _), 1.0);
+        let c = n - 3;
+        tree.set((c, c, c), 7);
+        let origin = Vec3::new(0.5, 0.5, 0.5);
+        let hit = tree.raycast(&origin, &Vec3::new(1.0, 1.0, 1.0), float::INFINITY).unwrap();
+        assert_eq!((hit.c_xyz, hit.block), ((c, c, c), 7));
+        // Misses terminate too.
+        assert!(tree.raycast(&origin, &Vec3::new(1.0, 0.9, 1.0), float::INFINITY).is_none());
+    }
+
+    #[test]
+    fn raycast_small_grid() {
+        diagonal_hit(16);
+    }
+
+    #[test]
+    fn raycast_large_grids() {
+        diagonal_hit(2048);
+        diagonal_hit(4096);
+    }
+
+    #[test]
+    fn raycast_axis_aligned() {
+        let mut tree = Octree::new((10, 10, 10), 1.0);
+        tree.set((2, 5, 5), 3);
+        tree.set((7, 5, 5), 4);
+        let hit = tree.raycast(&Vec3::new(9.5, 5.5, 5.5), &Vec3::new(-1.0, 0.0, 0.0), 100.0).unwrap();
+        assert_eq!((hit.c_xyz, hit.block), ((7, 5, 5), 4));
+        assert!((hit.distance - 1.5).abs() < 1e-4);
+        let hit = tree.raycast(&Vec3::new(-5.0, 5.5, 5.5), &Vec3::new(1.0, 0.0, 0.0), 100.0).unwrap();
+        assert_eq!(hit.c_xyz, (2, 5, 5));
+        assert!((hit.distance - 7.0).abs() < 1e-4);
+        assert!(tree.raycast(&Vec3::new(-5.0, 5.5, 5.5), &Vec3::new(1.0, 0.0, 0.0), 6.0).is_none());
+    }
+
+    #[test]
+    fn raycast_zero_direction() {
+        let mut tree = Octree::new((4, 4, 4), 1.0);
+        tree.set((0, 0, 0), 1);
+        assert!(tree.raycast(&Vec3::new(2.5, 2.5, 2.5), &Vec3::new(0.0, 0.0, 0.0), 10.0).is_none());
+    }
+
+    #[test]
+    fn iter_range_matches_grid() {
+        let mut arr = ArrayModel::new((9, 5, 12), 1.0);
+        for (i, c_xyz) in [(0, 0, 0), (8, 4, 11), (3, 2, 5), (4, 2, 5), (7, 0, 9), (1, 4, 10)].into_iter().enumerate() {
+            arr.set(c_xyz, i as Block + 1);
+        }
+        let mut tree = Octree::from_array(&arr);
+        // A uniform leaf larger than one voxel.
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 8..12 {
+                    tree.set((x, y, z), 9);
+                }
+            }
+        }
+        let arr = tree.to_array();
+        let (min, max) = ((1, 0, 4), (8, 3, 11));
+        let mut expected = arr
+            .iter_filled()
+            .filter(|&((x, y, z), _)| (min.0..max.0).contains(&x) && (min.1..max.1).contains(&y) && (min.2..max.2).contains(&z))
+            .collect::<Vec<_>>();
+        let mut found = tree.iter_range(min, max).collect::<Vec<_>>();
+        expected.sort();
+        found.sort();
+        assert_eq!(found, expected);
+        assert_eq!(VoxelGrid::iter_filled(&tree).count(), arr.iter_filled().count());
+        assert_eq!(tree.iter_range((0, 0, 0), (100, 100, 100)).count(), arr.iter_filled().count());
+    }
+
+    #[test]
+    fn array_round_trip() {
+        let mut arr = ArrayModel::new((5, 3, 6), 1.0);
+        arr.set((4, 2, 5), 2);
+        arr.set((0, 1, 3), -1);
+        let mut tree = Octree::from_array(&arr);
+        assert_eq!(tree.to_array().blocks, arr.blocks);
+        tree.set((4, 2, 5), 0);
+        tree.set((0, 1, 3), 0);
+        assert_eq!(tree.node_count(), 1);
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use crate::model2arr::Block;
+use crate::registry::BlockRegistry;
+
+/// Square images of every block, from its texture, else its registry colour, else
+/// `fallback_colour`.
+pub(crate) struct Swatches<'a> {
+    size: u32,
+    textures: HashMap<Block, &'a DynamicImage>,
+    registry: &'a BlockRegistry,
+    cache: HashMap<Block, RgbaImage>,
+}
+
+impl<'a> Swatches<'a> {
+    pub(crate) fn new(size: u32, textures: &'a [(Block, DynamicImage)], registry: &'a BlockRegistry) -> Self {
+        Self {
+            size,
+            textures: textures.iter().map(|(b, img)| (*b, img)).collect(),
+            registry,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, block: Block) -> &RgbaImage {
+        let (size, textures, registry) = (self.size, &self.textures, self.registry);
+        self.cache.entry(block).or_insert_with(|| match textures.get(&block) {
+            Some(img) => imageops::resize(&img.to_rgba8(), size, size, imageops::FilterType::Nearest),
+            None => {
+                let colour = registry.get(block).and_then(|def| def.colour).unwrap_or_else(|| fallback_colour(block));
+                RgbaImage::from_pixel(size, size, Rgba(colour))
+            }
+        })
+    }
+}
+
+/// Opaque colour derived from the block id, for blocks without a texture or registry colour.
+pub(crate) fn fallback_colour(block: Block) -> [u8; 4] {
+    let id = block as u16 as u32;
+    let channel = |k: u32| (id.wrapping_mul(k) % 160 + 60) as u8;
+    [channel(97), channel(57), channel(31), 255]
+}
+
+/// Alpha blends `src` over the pixel at (x, y).
+pub(crate) fn blend(img: &mut RgbaImage, x: u32, y: u32, src: Rgba<u8>) {
+    let dst = img.get_pixel_mut(x, y);
+    let a = src.0[3] as u32;
+    for c in 0..3 {
+        dst.0[c] = ((src.0[c] as u32 * a + dst.0[c] as u32 * (255 - a)) / 255) as u8;
+    }
+    dst.0[3] = dst.0[3].max(src.0[3]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{BlockDef, DEFAULT_BLOCK};
+
+    #[test]
+    fn swatch_sources() {
+        let mut registry = BlockRegistry::default();
+        registry.insert(2, BlockDef { colour: Some([1, 2, 3, 255]), ..BlockDef::new("painted") });
+        let textures = [(1, DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba([9, 9, 9, 255]))))];
+        let mut swatches = Swatches::new(2, &textures, &registry);
+        assert_eq!(swatches.get(1).dimensions(), (2, 2));
+        assert_eq!(swatches.get(1).get_pixel(0, 0).0, [9, 9, 9, 255]);
+        assert_eq!(swatches.get(2).get_pixel(1, 1).0, [1, 2, 3, 255]);
+        assert_eq!(swatches.get(DEFAULT_BLOCK).get_pixel(0, 0).0, fallback_colour(DEFAULT_BLOCK));
+    }
+
+    #[test]
+    fn blends_over_background() {
+        let mut img = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        blend(&mut img, 0, 0, Rgba([255, 255, 255, 0]));
+        assert_eq!(img.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        blend(&mut img, 0, 0, Rgba([255, 0, 0, 255]));
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    }
+}
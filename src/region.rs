@@ -0,0 +1,120 @@
+use crate::error::{Error, Result};
+use crate::grid::{offset_coord, GridTransform};
+use crate::model2arr::{uint, ArrayModel, CoordXYZ};
+use super::float;
+
+/// Inclusive (min, max) corners.
+pub type BoundingBox = ((usize, usize, usize), (usize, usize, usize));
+
+impl ArrayModel {
+    /// Bounding box of the non air voxels, `None` if the grid is empty.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let mut iter = self.iter_filled();
+        let (first, _) = iter.next()?;
+        Some(iter.fold((first, first), |(min, max), (c, _)| (
+            (min.0.min(c.0), min.1.min(c.1), min.2.min(c.2)),
+            (max.0.max(c.0), max.1.max(c.1), max.2.max(c.2)),
+        )))
+    }
+
+    /// Shrinks the grid to the bounding box of its non air voxels. An empty grid becomes 0x0x0.
+    pub fn crop_to_content(&self) -> ArrayModel {
+        match self.bounding_box() {
+            Some((min, max)) => {
+                let size = ((max.0 - min.0 + 1) as uint, (max.1 - min.1 + 1) as uint, (max.2 - min.2 + 1) as uint);
+                self.extract(min, size).expect("Bounding box is within the grid")
+            }
+            None => self.sub_grid((0, 0, 0), (0, 0, 0)),
+        }
+    }
+
+    /// Adds `n` voxels of air on every side.
+    pub fn pad(&self, n: usize) -> Result<ArrayModel> {
+        let grow = |d: uint| uint::try_from(d as usize + 2 * n).map_err(|_| Error::DimsTooLarge);
+        let dims = (grow(self.dims.0)?, grow(self.dims.1)?, grow(self.dims.2)?);
+        let mut transform = self.transform.clone();
+        transform.origin = transform.origin - transform.voxel_size.clone() * n as float;
+
+        let mut padded = ArrayModel::with_transform(dims, transform, self.resolution);
+        padded.registry = self.registry.clone();
+        padded.paste(self, (n as i64, n as i64, n as i64), false);
+        Ok(padded)
+    }
+
+    /// Copies the region starting at `min` of `size` voxels into a new grid, which is placed at the
+    /// same position in world space.
+    pub fn extract(&self, min: (usize, usize, usize), size: CoordXYZ) -> Result<ArrayModel> {
+        let max = (min.0 + size.0 as usize, min.1 + size.1 as usize, min.2 + size.2 as usize);
+        let fits = max.0 <= self.dims.0 as usize && max.1 <= self.dims.1 as usize && max.2 <= self.dims.2 as usize;
+        if !fits {
+            return Err(Error::OutOfBounds { c_xyz: max, dims: self.dims });
+        }
+        let mut region = self.sub_grid(min, size);
+        region.paste(self, (-(min.0 as i64), -(min.1 as i64), -(min.2 as i64)), false);
+        Ok(region)
+    }
+
+    /// Empty grid of `size` whose voxel (0, 0, 0) is this grid's voxel `min`.
+    fn sub_grid(&self, min: (usize, usize, usize), size: CoordXYZ) -> ArrayModel {
+        let transform = GridTransform::new(self.voxel_to_world(min), self.transform.voxel_size.clone());
+        let mut grid = ArrayModel::with_transform(size, transform, self.resolution);
+        grid.registry = self.registry.clone();
+        grid
+    }
+
+    /// Copies `src` into this grid with its voxel (0, 0, 0) at `offset`, parts outside of this grid
+    /// are clipped. With `mask_air`, air in `src` leaves the existing blocks untouched.
+    ///
+    /// Returns the number of voxels written.
+    pub fn paste(&mut self, src: &ArrayModel, offset: (i64, i64, i64), mask_air: bool) -> usize {
+        let mut written = 0;
+        for (i, &b) in src.blocks.iter().enumerate() {
+            if mask_air && b == 0 {
+                continue;
+            }
+            if let Some(c_xyz) = offset_coord(src.coord(i), offset, self.dims) {
+                self.set(c_xyz, b);
+                written += 1;
+            }
+        }
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_pad_extract() {
+        let mut arr = ArrayModel::new((6, 6, 6), 1.0);
+        arr.set((1, 2, 3), 1);
+        arr.set((2, 4, 3), 2);
+        assert_eq!(arr.bounding_box(), Some(((1, 2, 3), (2, 4, 3))));
+
+        let cropped = arr.crop_to_content();
+        assert_eq!(cropped.dims, (2, 3, 1));
+        assert_eq!(cropped.get((1, 2, 0)), Some(2));
+        assert_eq!(cropped.voxel_to_world((0, 0, 0)).y, 2.0);
+
+        let padded = cropped.pad(1).unwrap();
+        assert_eq!(padded.dims, (4, 5, 3));
+        assert_eq!(padded.get((1, 1, 1)), Some(1));
+        assert_eq!(padded.voxel_to_world((1, 1, 1)).x, 1.0);
+
+        assert!(arr.extract((5, 5, 5), (2, 1, 1)).is_err());
+        assert!(ArrayModel::new((2, 2, 2), 1.0).crop_to_content().blocks.is_empty());
+    }
+
+    #[test]
+    fn paste_clips_and_masks_air() {
+        let mut dst = ArrayModel::new((3, 3, 3), 1.0);
+        dst.blocks.fill(4);
+        let mut src = ArrayModel::new((2, 2, 2), 1.0);
+        src.set((0, 0, 0), 1);
+        assert_eq!(dst.paste(&src, (2, 2, 2), true), 1);
+        assert_eq!(dst.get((2, 2, 2)), Some(1));
+        assert_eq!(dst.paste(&src, (-1, 0, 0), false), 4);
+        assert_eq!(dst.get((0, 0, 0)), Some(0));
+    }
+}
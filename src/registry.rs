@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::model2arr::Block;
+use crate::TextureNames;
+
+pub const AIR: Block = 0;
+/// Block written by the voxelizer when no other block is requested.
+pub const DEFAULT_BLOCK: Block = -1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BlockDef {
+    pub name: String,
+    /// Texture file name, relative to the textures directory.
+    pub texture: Option<String>,
+    /// RGBA
+    pub colour: Option<[u8; 4]>,
+    /// Minecraft block state, e.g. `minecraft:red_terracotta`
+    pub minecraft_state: Option<String>,
+    #[serde(default)]
+    pub transparent: bool,
+    /// Falls when unsupported, like sand or gravel.
+    #[serde(default)]
+    pub gravity: bool,
+}
+
+impl BlockDef {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Default::default() }
+    }
+}
+
+/// Named definitions for every `Block` id used in a grid. Air (0) is implicit and cannot be
+/// redefined.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockRegistry {
+    blocks: BTreeMap<Block, BlockDef>,
+}
+
+impl Default for BlockRegistry {
+    /// Contains only the voxelizer's `DEFAULT_BLOCK`.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.insert(DEFAULT_BLOCK, BlockDef::new("default"));
+        registry
+    }
+}
+
+impl BlockRegistry {
+    pub fn empty() -> Self {
+        Self { blocks: BTreeMap::new() }
+    }
+
+    /// One block per texture, texture `i` gets id `i + 1`, matching `load_textures`.
+    pub fn from_textures(names: &TextureNames) -> Self {
+        let mut registry = Self::default();
+        for (i, fname) in names.textures().iter().enumerate() {
+            let name = fname.rsplit_once('.').map_or(fname.as_str(), |(name, _ext)| name);
+            registry.insert(i as Block + 1, BlockDef {
+                texture: Some(fname.clone()),
+                minecraft_state: Some(format!("minecraft:{}", name)),
+                ..BlockDef::new(name)
+            });
+        }
+        registry
+    }
+
+    /// Sets the colour of every block with a texture to the texture's average colour.
+    pub fn set_texture_colours(&mut self, textures: &[(Block, image::DynamicImage)]) {
+        for (block, img) in textures.iter() {
+            if let Some(def) = self.blocks.get_mut(block) {
+                def.colour = Some(average_colour(img));
+            }
+        }
+    }
+
+    /// Adds a block with the next free positive id.
+    pub fn register(&mut self, def: BlockDef) -> Block {
+        let id = self.blocks.keys().next_back().map_or(1, |&id| id.max(AIR) + 1);
+        self.blocks.insert(id, def);
+        id
+    }
+
+    /// Defines a block with a specific id, returning the previous definition. Air cannot be defined.
+    pub fn insert(&mut self, id: Block, def: BlockDef) -> Option<BlockDef> {
+        if id == AIR {
+            return None;
+        }
+        self.blocks.insert(id, def)
+    }
+
+    pub fn get(&self, id: Block) -> Option<&BlockDef> {
+        self.blocks.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: Block) -> Option<&mut BlockDef> {
+        self.blocks.get_mut(&id)
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<Block> {
+        if name == "air" {
+            return Some(AIR);
+        }
+        self.blocks.iter().find(|(_, def)| def.name == name).map(|(&id, _)| id)
+    }
+
+    /// Name of a block, "air" for air and "unknown" for undefined ids.
+    pub fn name(&self, id: Block) -> &str {
+        match id {
+            AIR => "air",
+            _ => self.get(id).map_or("unknown", |def| def.name.as_str()),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Block, &BlockDef)> {
+        self.blocks.iter().map(|(&id, def)| (id, def))
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+fn average_colour(img: &image::DynamicImage) -> [u8; 4] {
+    let img = img.to_rgba8();
+    let mut sum = [0u64; 4];
+    for p in img.pixels() {
+        for (s, &c) in sum.iter_mut().zip(p.0.iter()) {
+            *s += c as u64;
+        }
+    }
+    let n = (img.width() as u64 * img.height() as u64).max(1);
+    sum.map(|s| (s / n) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_textures_and_register() {
+        let names = TextureNames::new(vec!["stone.png".to_string(), "oak_planks.png".to_string()]);
+        let mut registry = BlockRegistry::from_textures(&names);
+        assert_eq!(registry.id_of("oak_planks"), Some(2));
+        assert_eq!(registry.get(1).unwrap().minecraft_state.as_deref(), Some("minecraft:stone"));
+        assert_eq!(registry.name(DEFAULT_BLOCK), "default");
+        assert_eq!((registry.name(AIR), registry.name(9)), ("air", "unknown"));
+
+        assert_eq!(registry.register(BlockDef::new("glass")), 3);
+        assert!(registry.insert(AIR, BlockDef::new("not air")).is_none());
+        assert_eq!(registry.id_of("air"), Some(AIR));
+        assert_eq!(registry.len(), 4);
+    }
+
+    #[test]
+    fn texture_colours() {
+        let mut registry = BlockRegistry::from_textures(&TextureNames::new(vec!["red.png".to_string()]));
+        let img = image::RgbaImage::from_pixel(2, 2, image::Rgba([200, 10, 0, 255]));
+        registry.set_texture_colours(&[(1, image::DynamicImage::ImageRgba8(img))]);
+        assert_eq!(registry.get(1).unwrap().colour, Some([200, 10, 0, 255]));
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+use image::{DynamicImage, Rgba, RgbaImage};
+use crate::coords::Axis;
+use crate::error::Result;
+use crate::grid::offset_coord;
+use crate::model2arr::{ArrayModel, Block};
+use crate::raster::{blend, Swatches};
+use crate::registry::AIR;
+use super::float;
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Width in pixels of a block's top face, rounded down to a multiple of 4.
+    pub tile: u32,
+    /// Quarter turns of the model about Y before rendering, as `ArrayModel::rotate_90`, to view
+    /// it from another side.
+    pub yaw_turns: u8,
+    /// Brightness of the top, x facing and z facing faces.
+    pub shading: [float; 3],
+    /// Empty pixels around the model.
+    pub margin: u32,
+    /// RGBA
+    pub background: [u8; 4],
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            tile: 16,
+            yaw_turns: 0,
+            shading: [1.0, 0.8, 0.6],
+            margin: 4,
+            background: [0, 0, 0, 0],
+        }
+    }
+}
+
+/// Renders the model isometrically, looking down at the x, z corner of the grid with x going to
+/// the bottom right and z to the bottom left. Faces are coloured like the cells of
+/// `guide::render_layer` and shaded per side by `RenderOptions::shading`.
+pub fn render_isometric(arr: &ArrayModel, textures: &[(Block, DynamicImage)], opts: &RenderOptions) -> RgbaImage {
+    let arr = arr.rotate_90(Axis::Y, opts.yaw_turns % 4).crop_to_content();
+    let (dx, dy, dz) = (arr.dims.0 as i64, arr.dims.1 as i64, arr.dims.2 as i64);
+    let tile = (opts.tile / 4).max(1) as i64 * 4;
+    let (h, q) = (tile / 2, tile / 4);
+    let margin = opts.margin as i64;
+
+    // Screen position of the grid corner (x, y, z).
+    let (ox, oy) = (dz * h + margin, dy * h + margin);
+    let project = |x: i64, y: i64, z: i64| (ox + (x - z) * h, oy + (x + z) * q - y * h);
+    let width = ((dx + dz) * h + 2 * margin) as u32;
+    let height = ((dx + dz) * q + dy * h + 2 * margin) as u32;
+    let mut img = RgbaImage::from_pixel(width, height, Rgba(opts.background));
+
+    let mut swatches = Swatches::new(tile as u32, textures, &arr.registry);
+    let mut voxels = arr.iter_filled().collect::<Vec<((usize, usize, usize), Block)>>();
+    // Painter's algorithm, cubes with the same x + y + z never overlap.
+    voxels.sort_by_key(|&((x, y, z), _)| x + y + z);
+    for ((x, y, z), b) in voxels {
+        let open = |d| offset_coord((x, y, z), d, arr.dims).and_then(|n| arr.get(n)).unwrap_or(AIR) == AIR;
+        let (xi, yi, zi) = (x as i64, y as i64, z as i64);
+        let swatch = swatches.get(b);
+        // (face origin, u edge, v edge, shade)
+        let faces = [
+            (open((0, 1, 0)), project(xi, yi + 1, zi), (h, q), (-h, q), opts.shading[0]),
+            (open((1, 0, 0)), project(xi + 1, yi + 1, zi), (-h, q), (0, h), opts.shading[1]),
+            (open((0, 0, 1)), project(xi, yi + 1, zi + 1), (h, q), (0, h), opts.shading[2]),
+        ];
+        for (visible, origin, u, v, shade) in faces {
+            if visible {
+                draw_face(&mut img, swatch, origin, u, v, shade);
+            }
+        }
+    }
+    img
+}
+
+/// Fills the parallelogram `origin + s * u + t * v` for s, t in [0, 1) with the texture.
+fn draw_face(img: &mut RgbaImage, texture: &RgbaImage, origin: (i64, i64), u: (i64, i64), v: (i64, i64), shade: float) {
+    let det = (u.0 * v.1 - u.1 * v.0) as float;
+    let xs = [0, u.0, v.0, u.0 + v.0];
+    let ys = [0, u.1, v.1, u.1 + v.1];
+    let (tw, th) = texture.dimensions();
+    for py in *ys.iter().min().unwrap()..*ys.iter().max().unwrap() {
+        for px in *xs.iter().min().unwrap()..*xs.iter().max().unwrap() {
+            // Sample at the pixel centre.
+            let (rx, ry) = (px as float + 0.5, py as float + 0.5);
+            let s = (rx * v.1 as float - ry * v.0 as float) / det;
+            let t = (ry * u.0 as float - rx * u.1 as float) / det;
+            if !(0.0..1.0).contains(&s) || !(0.0..1.0).contains(&t) {
+                continue;
+            }
+            let (x, y) = (origin.0 + px, origin.1 + py);
+            if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+                continue;
+            }
+            let mut p = *texture.get_pixel(((s * tw as float) as u32).min(tw - 1), ((t * th as float) as u32).min(th - 1));
+            for c in p.0.iter_mut().take(3) {
+                *c = (*c as float * shade).clamp(0.0, 255.0) as u8;
+            }
+            blend(img, x as u32, y as u32, p);
+        }
+    }
+}
+
+pub fn save_isometric<P>(arr: &ArrayModel, textures: &[(Block, DynamicImage)], path: P, opts: &RenderOptions) -> Result<()>
+where
+        P: AsRef<Path> + std::fmt::Debug, {
+    render_isometric(arr, textures, opts).save(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::DEFAULT_BLOCK;
+
+    #[test]
+    fn renders_default_block() {
+        let mut arr = ArrayModel::new((2, 2, 2), 1.0);
+        arr.set((0, 0, 0), DEFAULT_BLOCK);
+        let opts = RenderOptions { margin: 0, ..Default::default() };
+        let img = render_isometric(&arr, &[], &opts);
+        assert_eq!(img.dimensions(), (16, 16));
+        // Centre of the top face.
+        assert_eq!(img.get_pixel(8, 4).0[3], 255);
+        assert_eq!(img.get_pixel(0, 0).0[3], 0);
+    }
+
+    #[test]
+    fn renders_empty_grid() {
+        let arr = ArrayModel::new((2, 2, 2), 1.0);
+        let img = render_isometric(&arr, &[], &RenderOptions::default());
+        assert!(img.pixels().all(|p| p.0[3] == 0));
+    }
+}
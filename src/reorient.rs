@@ -0,0 +1,113 @@
+use crate::coords::Axis;
+use crate::model2arr::{ArrayModel, CoordXYZ};
+use crate::vec3::Vec3;
+
+impl ArrayModel {
+    /// Rotates the grid by `turns` * 90 degrees counter-clockwise (right handed) about `axis`.
+    ///
+    /// `dims` and the voxel size are swapped to match, the grid origin is kept.
+    pub fn rotate_90(&self, axis: Axis, turns: u8) -> ArrayModel {
+        let mut rotated = self.clone();
+        for _ in 0..turns % 4 {
+            rotated = rotated.rotate_90_once(axis);
+        }
+        rotated
+    }
+
+    fn rotate_90_once(&self, axis: Axis) -> ArrayModel {
+        let (dx, dy, dz) = (self.dims.0 as usize, self.dims.1 as usize, self.dims.2 as usize);
+        let s = &self.transform.voxel_size;
+        match axis {
+            Axis::X => self.remap(
+                (self.dims.0, self.dims.2, self.dims.1),
+                Vec3::new(s.x, s.z, s.y),
+                |(x, y, z)| (x, dz - 1 - z, y),
+            ),
+            Axis::Y => self.remap(
+                (self.dims.2, self.dims.1, self.dims.0),
+                Vec3::new(s.z, s.y, s.x),
+                |(x, y, z)| (z, y, dx - 1 - x),
+            ),
+            Axis::Z => self.remap(
+                (self.dims.1, self.dims.0, self.dims.2),
+                Vec3::new(s.y, s.x, s.z),
+                |(x, y, z)| (dy - 1 - y, x, z),
+            ),
+        }
+    }
+
+    /// Mirrors the grid along `axis`, e.g. `Axis::X` swaps the low and high X sides.
+    pub fn mirror(&self, axis: Axis) -> ArrayModel {
+        let (dx, dy, dz) = (self.dims.0 as usize, self.dims.1 as usize, self.dims.2 as usize);
+        let voxel_size = self.transform.voxel_size.clone();
+        match axis {
+            Axis::X => self.remap(self.dims, voxel_size, |(x, y, z)| (dx - 1 - x, y, z)),
+            Axis::Y => self.remap(self.dims, voxel_size, |(x, y, z)| (x, dy - 1 - y, z)),
+            Axis::Z => self.remap(self.dims, voxel_size, |(x, y, z)| (x, y, dz - 1 - z)),
+        }
+    }
+
+    /// Moves every voxel to `f(c_xyz)` in a new grid of `dims`.
+    fn remap<F>(&self, dims: CoordXYZ, voxel_size: Vec3, f: F) -> ArrayModel
+    where
+            F: Fn((usize, usize, usize)) -> (usize, usize, usize), {
+        let mut transform = self.transform.clone();
+        transform.voxel_size = voxel_size;
+        let mut remapped = ArrayModel::with_transform(dims, transform, self.resolution);
+        remapped.registry = self.registry.clone();
+        for (i, &b) in self.blocks.iter().enumerate() {
+            if b != 0 {
+                remapped.set(f(self.coord(i)), b);
+            }
+        }
+        remapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ArrayModel {
+        let mut arr = ArrayModel::new((2, 3, 4), 1.0);
+        arr.set((0, 0, 0), 1);
+        arr.set((1, 2, 3), 2);
+        arr.set((1, 0, 2), 3);
+        arr
+    }
+
+    #[test]
+    fn four_turns_are_identity() {
+        let arr = sample();
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let once = arr.rotate_90(axis, 1);
+            assert_eq!(once.iter_filled().count(), 3);
+            assert_eq!(once.rotate_90(axis, 3).blocks, arr.blocks);
+            assert_eq!(arr.rotate_90(axis, 2).rotate_90(axis, 2).dims, arr.dims);
+        }
+        assert_eq!(arr.rotate_90(Axis::Y, 1).dims, (4, 3, 2));
+    }
+
+    #[test]
+    fn quarter_turn_per_axis() {
+        let arr = sample();
+        let x = arr.rotate_90(Axis::X, 1);
+        assert_eq!(x.dims, (2, 4, 3));
+        assert_eq!((x.get((1, 1, 0)), x.get((1, 0, 2)), x.get((0, 3, 0))), (Some(3), Some(2), Some(1)));
+        let y = arr.rotate_90(Axis::Y, 1);
+        assert_eq!(y.dims, (4, 3, 2));
+        assert_eq!((y.get((2, 0, 0)), y.get((3, 2, 0)), y.get((0, 0, 1))), (Some(3), Some(2), Some(1)));
+        let z = arr.rotate_90(Axis::Z, 1);
+        assert_eq!(z.dims, (3, 2, 4));
+        assert_eq!((z.get((2, 1, 2)), z.get((0, 1, 3)), z.get((2, 0, 0))), (Some(3), Some(2), Some(1)));
+    }
+
+    #[test]
+    fn mirror_twice_is_identity() {
+        let arr = sample();
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            assert_eq!(arr.mirror(axis).mirror(axis).blocks, arr.blocks);
+        }
+        assert_eq!(arr.mirror(Axis::X).get((1, 0, 0)), Some(1));
+    }
+}